@@ -1,5 +1,9 @@
+use strum::FromRepr;
+
+use crate::events::{Direction, Role};
+
 #[repr(u8)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, FromRepr, PartialEq, Eq, Hash)]
 pub enum NetworkEvent {
     //NOT_IMPLEMENTED_ReleaseLocalAI = 62,
     //NOT_USED_SelfExitedCapturePoint = 96,
@@ -159,3 +163,176 @@ pub enum NetworkEvent {
     UpdateVotingAfterBattle = 171,
     CosmeticAction = 172,
 }
+
+impl NetworkEvent {
+    /// Which way the event travels, or `None` for the connection callbacks
+    /// the game raises locally and never sends.
+    pub const fn direction(self) -> Option<Direction> {
+        use NetworkEvent::*;
+        match self {
+            OnFailedToConnectToMasterServer
+            | OnConnectingToLobbyServer
+            | OnConnectedToLobbyServer
+            | OnDisconnectingFromLobbyServer
+            | OnDisconnectedFromLobbyServer
+            | OnConnectedToServer
+            | OnFailedToConnectToServer
+            | OnConnectionLost
+            | OnDisconnectedFromServer
+            | OnConnectedToGameServer
+            | OnServerStarted
+            | OnServerStopped
+            | OnPlayerConnectedToServer
+            | OnPlayerDisconnectedFromServer => None,
+            // Requests and reports from a client. Most `Broadcast*` events are
+            // relayed to the other clients under a code of their own.
+            RequestRespawnPoint
+            | RequestTeamBaseModel
+            | RequestCapturePoints
+            | RequestEqualizerModel
+            | MachineDestroyed
+            | OnPlayerInputChanged
+            | DamageCube
+            | GetClientPings
+            | EnemySpotted
+            | AssistBonusRequest
+            | KillBonusRequest
+            | EACRegisterToken
+            | HeallingAssistBonusRequest
+            | ProtectTeamMateBonusRequest
+            | DefendTheBaseBonusRequest
+            | DestroyCubesBonusRequest
+            | HealCubesBonusRequest
+            | AwardTeamBaseProtoniumDestroyedRequest
+            | SurrenderRequest
+            | HealSelf
+            | SurrenderVoteCast
+            | ValidateGameGuid
+            | LockOnNotification
+            | SpawnShield
+            | BroadcastOpenShield
+            | BroadcastInvisible
+            | BroadcastVisible
+            | BroadcastActivateTeleportEffect
+            | BroadcastActivateReadyEffect
+            | BroadcastSpawnEmpLocator
+            | BroadcastSpawnEmpMachineEffect
+            | WeaponSelect
+            | HealAlly
+            | SelfDestructClassicMode
+            | ClientDisconnecting
+            | RequestLoadingProgressAllUsers
+            | LoadingComplete
+            | SendDamagedByEnemyShield
+            | RadarModuleActivated
+            | RequestSync
+            | PlayerQuitRequest
+            | DamageCubeEffectOnly
+            | DamageCubeNoEffect => Some(Direction::ToServer),
+            // Sent by either side, usually a client's own action echoed to
+            // the others by the server.
+            FireMiss
+            | MultipleFireMisses
+            | AlignmentRectifierStarted
+            | MapPingEvent
+            | TestConnection
+            | EnergyModuleActivated
+            | BroadcastLoadingProgress
+            | EACMessage
+            | Taunt
+            | MachineFullHealth
+            | CosmeticAction => Some(Direction::Both),
+            FreeSpawnPoint
+            | TeamBase
+            | RegisterCapturePoints
+            | RegisterEqualizer
+            | FreeRespawnPoint
+            | PlayerIDs
+            | SyncMachineCubes
+            | GameStarted
+            | OnServerReceivedInputChange
+            | ClientUnregistered
+            | OnAnotherClientDisconnected
+            | OnClientReconnected
+            | FireWeaponEffect
+            | CurrentGameTime
+            | EndGame
+            | GameWon
+            | GameLost
+            | GameWonBaseDestroyed
+            | GameLostBaseDestroyed
+            | BuffTeamPlayers
+            | PlayerThreateningBase
+            | TimeToGameStart
+            | SetRespawnWaitingTime
+            | TeamBaseState
+            | TeamBaseCaptureStart
+            | TeamBaseCaptureReset
+            | TeamBaseCaptureStop
+            | TeamBaseSectionComplete
+            | TeamBaseFinalSectionComplete
+            | TeamBaseInitialise
+            | SetClientPing
+            | WarnPlayer
+            | RemoteEnemySpotted
+            | TeamBaseContested
+            | AcquireRemoteAI
+            | DestroyHealCubesPointsAwarded
+            | ConfirmedKill
+            | BonusesFlushDone
+            | SetShieldState
+            | TeamBaseLowHealth
+            | InitialiseGameStats
+            | UpdateGameStats
+            | HealSelfResponse
+            | SurrenderVoteStarted
+            | CurrentSurrenderVotes
+            | SurrenderAccepted
+            | SurrenderDeclined
+            | SetSurrenderTimes
+            | SetFinalGameScore
+            | PitLeaderBoardUpdate
+            | PitModeState
+            | GameGuidValidated
+            | PlayerInsideBase
+            | ConfirmedAssist
+            | LockOnNotificationBroadcast
+            | ShieldSpawned
+            | OpenShield
+            | MakeInvisible
+            | MakeVisible
+            | ActivateTeleportEffect
+            | ActivateReadyEffect
+            | SpawnEmpLocator
+            | SpawnEmpMachineEffect
+            | BroadcastWeaponSelect
+            | HostAIs
+            | HealAllyResponse
+            | GameModeSettings
+            | TeamDeathMatchState
+            | SendBonus
+            | MachineDestroyedConfirmed
+            | GameAborted
+            | DamagedByEnemyShield
+            | EqualizerNotification
+            | CapturePointProgress
+            | CapturePointNotification
+            | RemoteRadarModuleActivated
+            | BeginSync
+            | EndOfSync
+            | SyncTeamBaseCubes
+            | SyncEqualizerNotification
+            | PlayerQuitRequestComplete
+            | DestroyCubeEffectOnly
+            | DestroyCubeNoEffect
+            | DestroyCubesFull
+            | LongPlayValue
+            | UpdateVotingAfterBattle => Some(Direction::ToClient),
+        }
+    }
+    /// Whether `role` may receive this event.
+    pub fn accepted_by(self, role: Role) -> bool {
+        self.direction()
+            .is_some_and(|direction| direction.accepted_by(role))
+    }
+}
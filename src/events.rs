@@ -1,113 +1,309 @@
 use crate::{
+    event_code::NetworkEvent,
     events::{
         ingame::{
-            CosmeticAction, CurrentSurrenderVotes, DestroyCubeEffectOnly, DestroyCubeNoEffect,
+            BonusAwarded, CosmeticAction, CubesBonus, CurrentSurrenderVotes, DamageCube,
+            DamageCubeEffectOnly, DamageCubeNoEffect, DestroyCubeEffectOnly, DestroyCubeNoEffect,
             DestroyCubesFull, FireMiss, GameEnd, GameLoseWin, GameStart, HealAllyCubes, Kill,
             LockOnNotifier, MapPing, MultiPlayerInputChanged, MultipleFireMisses,
-            NetworkStunnedMachineEffect, PlayerId, RequestPing, RespawnTime, SelectWeapon,
-            SetFinalGameScore, SpawnEmpLocator, SurrenderDeclined, SurrenderTimes, Taunt,
-            TeamBaseBoolean, TeamBaseState, TeleportActivateEffect, UpdateGameStats,
-            UpdateVotingAfterBattle, WeaponFireEffect,
+            NetworkStunnedMachineEffect, OpenShield, PlayerBonus, PlayerId, RequestPing,
+            RespawnTime, SelectWeapon, SetFinalGameScore, ShieldModuleEvent, ShieldSpawned,
+            SpawnEmpLocator, SurrenderDeclined, SurrenderTimes, Taunt, TeamBaseBoolean,
+            TeamBaseState, TeleportActivateEffect, UpdateGameStats, UpdateVotingAfterBattle,
+            WeaponFireEffect,
         },
-        loading::{LoadingProgress, PlayerIDs, PlayerIDsAndNames},
+        loading::{GameGuid, LoadingProgress, PlayerIDs, PlayerIDsAndNames},
         sync::{
             EqualizerNotification, FusionShieldState, GetCapturePoints, GetEqualizer, GetTeamBase,
             InitialiseGameStats, SpawnPoint, SyncMachineCubes, UpdateGameModeSettings,
             UpdateTeamDeathMatch, UpdateTeamDeathmatchSettings,
         },
     },
+    net::{EventError, decode_event, encode_event},
+    photon::enter_battle::GameModeType,
     types::{HitCubeInfo, StringCode, TargetType},
 };
+use byteserde::{
+    des_slice::from_slice,
+    prelude::{ByteDeserializeSlice, ByteSerializeHeap},
+};
 use byteserde_derive::{ByteDeserializeSlice, ByteSerializeHeap};
 use sealed::sealed;
 pub mod ingame;
 pub mod loading;
 pub mod sync;
 
-pub trait GameMode {}
+pub trait GameMode {
+    /// Every event registered for this mode, with its decoded payload.
+    type Event: std::fmt::Debug + Clone;
+    /// What `EnterBattle` calls this mode.
+    const TYPE: GameModeType;
+    /// Decodes an event body received by `role`, rejecting codes that aren't
+    /// registered for this mode or aren't sent towards `role`.
+    fn decode(role: Role, code: NetworkEvent, body: &[u8]) -> Result<Self::Event, EventError>;
+}
 pub enum BattleArena {}
-impl GameMode for BattleArena {}
 pub enum TeamDeathMatch {}
-impl GameMode for TeamDeathMatch {}
+/// What `EnterBattle` calls [`GameModeType::SuddenDeath`].
 pub enum Elimination {}
-impl GameMode for Elimination {}
+/// [`Elimination`] under its `EnterBattle` name. It's an alias rather than a
+/// marker of its own because both names are one mode with one set of
+/// registrations: a separate marker would have to repeat every registration,
+/// and its typed events wouldn't be interchangeable with `Elimination`'s.
+pub type SuddenDeath = Elimination;
 pub enum Pit {}
-impl GameMode for Pit {}
+/// The solo test drive.
+pub enum TestMode {}
+/// Team deathmatch against AI.
+pub enum SinglePlayerTDM {}
+pub enum Campaign {}
+
+/// The runtime counterpart of [`TypedEvent`]: any event `M` allows.
+pub type AnyEvent<M> = <M as GameMode>::Event;
 
 pub struct ConstEvent<const N: u8>;
 
+/// Which side of a connection this end is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Role {
+    Server,
+    Client,
+}
+
+impl Role {
+    /// The role at the other end of the connection.
+    pub fn remote(self) -> Role {
+        match self {
+            Role::Server => Role::Client,
+            Role::Client => Role::Server,
+        }
+    }
+}
+
+/// Which way an event may travel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    ToServer,
+    ToClient,
+    Both,
+}
+
+impl Direction {
+    /// Whether `role` may receive events going this way.
+    pub fn accepted_by(self, role: Role) -> bool {
+        matches!(
+            (self, role),
+            (Direction::Both, _)
+                | (Direction::ToServer, Role::Server)
+                | (Direction::ToClient, Role::Client)
+        )
+    }
+}
+
 #[sealed(pub(crate))]
 pub trait TypedEvent<Mode: GameMode> {
-    type Data;
-    //fn parse(&self, data: &[u8]) -> Self::Data;
+    type Data: ByteSerializeHeap + ByteDeserializeSlice<Self::Data>;
+    const CODE: NetworkEvent;
+    const DIRECTION: Direction;
+
+    /// Encodes a whole event message, code included.
+    fn encode(data: &Self::Data) -> Result<Vec<u8>, EventError> {
+        encode_event(Self::CODE, data)
+    }
+    /// Decodes a whole event message, which must carry [`Self::CODE`].
+    fn decode(payload: &[u8]) -> Result<Self::Data, EventError> {
+        let (code, body) = decode_event(payload)?;
+        if code != Self::CODE {
+            return Err(EventError::UnexpectedCode {
+                expected: Self::CODE,
+                found: code,
+            });
+        }
+        Ok(from_slice(body)?)
+    }
 }
 
 macro_rules! register_event_types {
-    (dollar = $_:tt; $( , )? ) => {};
     {
         #[dollar = $_:tt]
+        modes: $modes:tt
         $( $rest:tt )*
     } => {
-        crate::events::register_event_types! {dollar = $_; $( $rest )* }
+        crate::events::register_event_types! { @munch dollar = $_; modes = $modes; blocks = []; $( $rest )* }
     };
     (
-        dollar = $_:tt;
+        @munch dollar = $_:tt; modes = $modes:tt; blocks = $blocks:tt;
         , $( $rest:tt )*
     ) => {
-        crate::events::register_event_types! { dollar = $_; $( $rest )* }
+        crate::events::register_event_types! { @munch dollar = $_; modes = $modes; blocks = $blocks; $( $rest )* }
+    };
+    (
+        @munch dollar = $_:tt; modes = $modes:tt; blocks = $blocks:tt;
+        $( $bound:ident ),+ : $body:tt $( $rest:tt )*
+    ) => {
+        crate::events::register_event_types! {
+            @block dollar = $_; modes = $modes; blocks = $blocks;
+            [ $( $bound )+ ] $body $( $rest )*
+        }
     };
     (
-        dollar = $_:tt;
-        All: {
+        @block dollar = $_:tt; modes = $modes:tt; blocks = [ $( $block:tt )* ];
+        $bounds:tt {
             $(
-                { $ty:ty: $( $event:ident ),+ $( , )? }
+                { $direction:ident $ty:ty: $( $event:ident ),+ $( , )? }
             ),+
             $( , )?
         } $( $rest:tt )*
     ) => {
         $(
             $(
-                #[::sealed::sealed]
-            impl<M: crate::events::GameMode> crate::events::TypedEvent<M> for crate::events::ConstEvent<{ crate::event_code::NetworkEvent::$event as u8 }> {
-                type Data = $ty;
-            }
+                crate::events::register_event_types! { @impl $bounds $direction $ty, $event }
             )+
         )+
-        crate::events::register_event_types! { dollar = $_; $( $rest )* }
+        crate::events::register_event_types! {
+            @munch dollar = $_; modes = $modes;
+            blocks = [ $( $block )* { $bounds [ $( ( $direction $ty; $( $event )+ ) )+ ] } ];
+            $( $rest )*
+        }
     };
+    // Every block has been seen, so each mode's decoder can pick out the
+    // blocks that apply to it.
     (
-        dollar = $_:tt;
-        $( $bound:ident ),+ : {
-            $(
-                { $ty:ty: $( $event:ident ),+ $( , )? }
-            ),+
-            $( , )?
-        } $( $rest:tt )*
+        @munch dollar = $_:tt;
+        modes = { $( $mode:ident: $any_event:ident = $mode_type:ident ),+ $( , )? };
+        blocks = $blocks:tt;
     ) => {
-        macro_rules! inner {
-            ($_bound:ident) => {
-                $(
+        /// An event decoded for a game mode only known at runtime.
+        #[derive(Debug, Clone)]
+        pub enum ModeEvent {
+            $( $mode($any_event), )+
+        }
+        impl ModeEvent {
+            /// Decodes with the marker for the mode `EnterBattle` keyed the
+            /// battle on, as received by `role`. Ranked and custom battles
+            /// register the same events as the mode they're keyed on, so
+            /// only `key.ty` picks the marker.
+            pub fn decode(
+                key: crate::photon::enter_battle::GameModeKey,
+                role: crate::events::Role,
+                code: crate::event_code::NetworkEvent,
+                body: &[u8],
+            ) -> Result<Self, crate::net::EventError> {
+                match key.ty {
                     $(
-                        #[::sealed::sealed]
-                        impl crate::events::TypedEvent<crate::events::$_bound> for crate::events::ConstEvent<{ crate::event_code::NetworkEvent::$event as u8 }> {
-                            type Data = $ty;
+                        crate::photon::enter_battle::GameModeType::$mode_type => {
+                            <crate::events::$mode as crate::events::GameMode>::decode(role, code, body).map(Self::$mode)
                         }
                     )+
-                )+
+                }
+            }
+            pub fn code(&self) -> crate::event_code::NetworkEvent {
+                match self {
+                    $( Self::$mode(event) => event.code(), )+
+                }
             }
         }
         $(
-            inner! { $bound }
+            macro_rules! select {
+                (@start [ $_( $_ block:tt )* ]) => {
+                    select! { [] $_( $_ block )* }
+                };
+                ([ $_( $_ out:tt )* ]) => {
+                    crate::events::register_event_types! { @emit $mode, $any_event, $mode_type; $_( $_ out )* }
+                };
+                ([ $_( $_ out:tt )* ] { [] $_ groups:tt } $_( $_ rest:tt )*) => {
+                    select! { [ $_( $_ out )* ] $_( $_ rest )* }
+                };
+                ([ $_( $_ out:tt )* ] { [ All ] [ $_( $_ group:tt )* ] } $_( $_ rest:tt )*) => {
+                    select! { [ $_( $_ out )* $_( $_ group )* ] $_( $_ rest )* }
+                };
+                ([ $_( $_ out:tt )* ] { [ $mode $_( $_ bound:ident )* ] [ $_( $_ group:tt )* ] } $_( $_ rest:tt )*) => {
+                    select! { [ $_( $_ out )* $_( $_ group )* ] $_( $_ rest )* }
+                };
+                ([ $_( $_ out:tt )* ] { [ $_ other:ident $_( $_ bound:ident )* ] $_ groups:tt } $_( $_ rest:tt )*) => {
+                    select! { [ $_( $_ out )* ] { [ $_( $_ bound )* ] $_ groups } $_( $_ rest )* }
+                };
+            }
+            select! { @start $blocks }
         )+
-        crate::events::register_event_types! { dollar = $_; $( $rest )* }
+    };
+    (@impl [ All ] $direction:ident $ty:ty, $event:ident) => {
+        crate::events::register_event_types! { @check $direction $event }
+        #[::sealed::sealed]
+        impl<M: crate::events::GameMode> crate::events::TypedEvent<M> for crate::events::ConstEvent<{ crate::event_code::NetworkEvent::$event as u8 }> {
+            type Data = $ty;
+            const CODE: crate::event_code::NetworkEvent = crate::event_code::NetworkEvent::$event;
+            const DIRECTION: crate::events::Direction = crate::events::Direction::$direction;
+        }
+    };
+    (@impl [ $( $bound:ident )+ ] $direction:ident $ty:ty, $event:ident) => {
+        crate::events::register_event_types! { @check $direction $event }
+        $(
+            #[::sealed::sealed]
+            impl crate::events::TypedEvent<crate::events::$bound> for crate::events::ConstEvent<{ crate::event_code::NetworkEvent::$event as u8 }> {
+                type Data = $ty;
+                const CODE: crate::event_code::NetworkEvent = crate::event_code::NetworkEvent::$event;
+                const DIRECTION: crate::events::Direction = crate::events::Direction::$direction;
+            }
+        )+
+    };
+    // A registration has to agree with `NetworkEvent::direction`.
+    (@check $direction:ident $event:ident) => {
+        const _: () = assert!(matches!(
+            crate::event_code::NetworkEvent::$event.direction(),
+            Some(crate::events::Direction::$direction)
+        ));
+    };
+    (@emit $mode:ident, $any_event:ident, $mode_type:ident; $( ( $direction:ident $ty:ty; $( $event:ident )+ ) )*) => {
+        #[doc = concat!("An event registered for [`", stringify!($mode), "`], decoded at runtime.")]
+        #[derive(Debug, Clone)]
+        pub enum $any_event {
+            $(
+                $( $event($ty), )+
+            )*
+        }
+        impl $any_event {
+            pub fn code(&self) -> crate::event_code::NetworkEvent {
+                match self {
+                    $(
+                        $( Self::$event(_) => crate::event_code::NetworkEvent::$event, )+
+                    )*
+                }
+            }
+        }
+        impl crate::events::GameMode for crate::events::$mode {
+            type Event = $any_event;
+            const TYPE: crate::photon::enter_battle::GameModeType = crate::photon::enter_battle::GameModeType::$mode_type;
+            fn decode(
+                role: crate::events::Role,
+                code: crate::event_code::NetworkEvent,
+                body: &[u8],
+            ) -> Result<$any_event, crate::net::EventError> {
+                match code {
+                    $(
+                        $(
+                            crate::event_code::NetworkEvent::$event => {
+                                if !crate::events::Direction::$direction.accepted_by(role) {
+                                    return Err(crate::net::EventError::WrongDirection(code));
+                                }
+                                Ok($any_event::$event(::byteserde::des_slice::from_slice(body)?))
+                            }
+                        )+
+                    )*
+                    _ => Err(crate::net::EventError::Unregistered(code)),
+                }
+            }
+        }
     };
 
-
-    ($ty:ty, $( $event:ident ),+ ) => {
+    ($direction:ident $ty:ty, $( $event:ident ),+ ) => {
         $(
+            crate::events::register_event_types! { @check $direction $event }
             #[::sealed::sealed]
             impl<M: crate::events::GameMode> crate::events::TypedEvent<M> for crate::events::ConstEvent<{ crate::event_code::NetworkEvent::$event as u8 }> {
                 type Data = $ty;
+                const CODE: crate::event_code::NetworkEvent = crate::event_code::NetworkEvent::$event;
+                const DIRECTION: crate::events::Direction = crate::events::Direction::$direction;
             }
         )+
     };
@@ -116,99 +312,130 @@ pub(crate) use register_event_types;
 
 register_event_types! {
     #[dollar = $]
+    modes: {
+        BattleArena: BattleArenaEvent = BattleArena,
+        TeamDeathMatch: TeamDeathMatchEvent = TeamDeathmatch,
+        Elimination: EliminationEvent = SuddenDeath,
+        Pit: PitEvent = Pit,
+        TestMode: TestModeEvent = TestMode,
+        SinglePlayerTDM: SinglePlayerTDMEvent = SinglePlayerTDM,
+        Campaign: CampaignEvent = Campaign,
+    }
     // Sync events
-    BattleArena, TeamDeathMatch, Elimination: {
-        { GameTime: CurrentGameTime },
+    BattleArena, TeamDeathMatch, Elimination, SinglePlayerTDM: {
+        { ToClient GameTime: CurrentGameTime },
     }
     BattleArena, Elimination, Pit: {
-        {UpdateGameModeSettings: GameModeSettings }
+        { ToClient UpdateGameModeSettings: GameModeSettings }
     }
-    TeamDeathMatch: {
-        { UpdateTeamDeathmatchSettings: GameModeSettings },
-        { UpdateTeamDeathMatch: TeamDeathMatchState },
+    TeamDeathMatch, SinglePlayerTDM: {
+        { ToClient UpdateTeamDeathmatchSettings: GameModeSettings },
+        { ToClient UpdateTeamDeathMatch: TeamDeathMatchState },
     }
     BattleArena: {
-        { GetTeamBase: TeamBase },
-        { GetCapturePoints: RegisterCapturePoints },
-        { GetEqualizer: RegisterEqualizer },
-        { FusionShieldState: SetShieldState },
-        { HealedCubes: SyncTeamBaseCubes },
-        { EqualizerNotification: EqualizerNotification },
+        { ToClient GetTeamBase: TeamBase },
+        { ToClient GetCapturePoints: RegisterCapturePoints },
+        { ToClient GetEqualizer: RegisterEqualizer },
+        { ToClient FusionShieldState: SetShieldState },
+        { ToClient HealedCubes: SyncTeamBaseCubes },
+        { ToClient EqualizerNotification: EqualizerNotification },
     }
     All: {
-        { InitialiseGameStats: InitialiseGameStats },
-        { SpawnPoint: FreeSpawnPoint },
-        { SyncMachineCubes: SyncMachineCubes },
-        { CommandOnly: BeginSync, EndOfSync },
+        { ToClient InitialiseGameStats: InitialiseGameStats },
+        { ToClient SpawnPoint: FreeSpawnPoint },
+        { ToClient SyncMachineCubes: SyncMachineCubes },
+        { ToClient CommandOnly: BeginSync, EndOfSync },
     }
     // Loading events
     All: {
-        { PlayerIDsAndNames: PlayerIDs },
-        { PlayerIDs: HostAIs },
-        { StringCode: WarnPlayer },
-        { LoadingProgress: BroadcastLoadingProgress },
+        { ToClient PlayerIDsAndNames: PlayerIDs },
+        { ToClient PlayerIDs: HostAIs },
+        { ToClient StringCode: WarnPlayer },
+        // Clients report their own progress and the server relays it.
+        { Both LoadingProgress: BroadcastLoadingProgress },
+        { ToServer GameGuid: ValidateGameGuid },
+        { ToClient CommandOnly: GameGuidValidated },
     }
     // Ingame events
     All: {
-        { SetFinalGameScore: SetFinalGameScore },
-        { UpdateGameStats: UpdateGameStats },
-        { UpdateVotingAfterBattle: UpdateVotingAfterBattle },
-        { Kill: MachineDestroyedConfirmed },
-        { MultiPlayerInputChanged: OnServerReceivedInputChange },
-        { DestroyCubesFull: DestroyCubesFull },
-        { DestroyCubeEffectOnly: DestroyCubeEffectOnly },
-        { DestroyCubeNoEffect: DestroyCubeNoEffect },
-        { WeaponFireEffect: FireWeaponEffect },
-        { FireMiss: FireMiss },
-        { MultipleFireMisses: MultipleFireMisses },
-        { GameTime: TimeToGameStart },
-        { GameStart: GameStarted },
-        { GameEnd: EndGame },
-        { RequestPing: GetClientPings, SetClientPing },
-        { PlayerId: AlignmentRectifierStarted },
-        { MapPing: MapPingEvent },
-        { HealedCubes: HealSelfResponse },
-        { Kill: ConfirmedKill, ConfirmedAssist },
-        { LockOnNotifier: LockOnNotificationBroadcast },
-        { TeleportActivateEffect: ActivateTeleportEffect },
-        { SpawnEmpLocator: SpawnEmpLocator },
-        { NetworkStunnedMachineEffect: SpawnEmpMachineEffect },
-        { Taunt: Taunt },
-        { CosmeticAction: CosmeticAction },
-        { SelectWeapon: BroadcastWeaponSelect },
-        { HealAllyCubes: HealAllyResponse },
+        { ToClient SetFinalGameScore: SetFinalGameScore },
+        { ToClient UpdateGameStats: UpdateGameStats },
+        { ToClient UpdateVotingAfterBattle: UpdateVotingAfterBattle },
+        { ToClient Kill: MachineDestroyedConfirmed },
+        { ToClient MultiPlayerInputChanged: OnServerReceivedInputChange },
+        { ToClient DestroyCubesFull: DestroyCubesFull },
+        { ToClient DestroyCubeEffectOnly: DestroyCubeEffectOnly },
+        { ToClient DestroyCubeNoEffect: DestroyCubeNoEffect },
+        { ToServer DamageCube: DamageCube },
+        { ToServer DamageCubeEffectOnly: DamageCubeEffectOnly },
+        { ToServer DamageCubeNoEffect: DamageCubeNoEffect },
+        { ToClient WeaponFireEffect: FireWeaponEffect },
+        { Both FireMiss: FireMiss },
+        { Both MultipleFireMisses: MultipleFireMisses },
+        { ToClient GameTime: TimeToGameStart },
+        { ToClient GameStart: GameStarted },
+        { ToClient GameEnd: EndGame },
+        { ToServer RequestPing: GetClientPings },
+        { ToClient RequestPing: SetClientPing },
+        { Both PlayerId: AlignmentRectifierStarted },
+        { Both MapPing: MapPingEvent },
+        { ToClient HealedCubes: HealSelfResponse },
+        { ToClient Kill: ConfirmedKill, ConfirmedAssist },
+        { ToClient LockOnNotifier: LockOnNotificationBroadcast },
+        { ToClient TeleportActivateEffect: ActivateTeleportEffect },
+        { ToClient SpawnEmpLocator: SpawnEmpLocator },
+        { ToServer ShieldModuleEvent: SpawnShield },
+        { ToClient ShieldSpawned: ShieldSpawned },
+        { ToServer OpenShield: BroadcastOpenShield },
+        { ToClient OpenShield: OpenShield },
+        { ToClient NetworkStunnedMachineEffect: SpawnEmpMachineEffect },
+        { Both Taunt: Taunt },
+        { Both CosmeticAction: CosmeticAction },
+        { ToClient SelectWeapon: BroadcastWeaponSelect },
+        { ToClient HealAllyCubes: HealAllyResponse },
         {
-            PlayerId:
+            ToClient PlayerId:
             MakeInvisible,
             MakeVisible,
-            EnergyModuleActivated,
             RemoteRadarModuleActivated,
             OnAnotherClientDisconnected,
             OnClientReconnected,
             AcquireRemoteAI,
         },
-        { CommandOnly: PlayerQuitRequestComplete }
+        { Both PlayerId: EnergyModuleActivated },
+        { ToClient CommandOnly: PlayerQuitRequestComplete },
+        // Bonuses, see `ingame::bonus_stat`
+        { ToServer PlayerBonus: KillBonusRequest, AssistBonusRequest, HeallingAssistBonusRequest },
+        {
+            ToServer CubesBonus:
+            ProtectTeamMateBonusRequest,
+            DestroyCubesBonusRequest,
+            HealCubesBonusRequest,
+        },
+        { ToClient BonusAwarded: DestroyHealCubesPointsAwarded, SendBonus },
+        { ToClient CommandOnly: BonusesFlushDone },
     }
-    BattleArena, Pit, TeamDeathMatch: {
-        { SpawnPoint: FreeRespawnPoint },
-        { RespawnTime: SetRespawnWaitingTime },
+    BattleArena, Pit, TeamDeathMatch, TestMode, SinglePlayerTDM, Campaign: {
+        { ToClient SpawnPoint: FreeRespawnPoint },
+        { ToClient RespawnTime: SetRespawnWaitingTime },
     }
-    BattleArena, Pit, TeamDeathMatch, Elimination: {
-        { GameLoseWin: GameLost, GameWon },
+    BattleArena, Pit, TeamDeathMatch, Elimination, SinglePlayerTDM, Campaign: {
+        { ToClient GameLoseWin: GameLost, GameWon },
     }
     BattleArena, TeamDeathMatch: {
-        { CurrentSurrenderVotes: SurrenderVoteStarted, CurrentSurrenderVotes },
-        { SurrenderDeclined: SurrenderDeclined },
-        { SurrenderTimes: SetSurrenderTimes },
+        { ToClient CurrentSurrenderVotes: SurrenderVoteStarted, CurrentSurrenderVotes },
+        { ToClient SurrenderDeclined: SurrenderDeclined },
+        { ToClient SurrenderTimes: SetSurrenderTimes },
     }
-    BattleArena, TeamDeathMatch, Elimination: {
-        { PlayerId: RemoteEnemySpotted }
+    BattleArena, TeamDeathMatch, Elimination, SinglePlayerTDM: {
+        { ToClient PlayerId: RemoteEnemySpotted }
     }
     BattleArena: {
-        { GameLoseWin: GameLostBaseDestroyed, GameWonBaseDestroyed },
-        { TeamBaseBoolean: PlayerInsideBase, TeamBaseContested },
+        { ToClient GameLoseWin: GameLostBaseDestroyed, GameWonBaseDestroyed },
+        { ToClient TeamBaseBoolean: PlayerInsideBase, TeamBaseContested },
+        { ToServer CubesBonus: DefendTheBaseBonusRequest, AwardTeamBaseProtoniumDestroyedRequest },
         {
-            TeamBaseState:
+            ToClient TeamBaseState:
             TeamBaseState,
             TeamBaseCaptureStart,
             TeamBaseCaptureStop,
@@ -235,3 +462,61 @@ pub struct HealedCubes {
 }
 #[derive(Debug, Default, Clone, Copy, ByteDeserializeSlice, ByteSerializeHeap)]
 pub struct CommandOnly;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::photon::enter_battle::GameModeKey;
+
+    fn key(ty: GameModeType, is_ranked: bool, is_custom: bool) -> GameModeKey {
+        GameModeKey {
+            ty,
+            is_ranked,
+            is_custom,
+        }
+    }
+
+    #[test]
+    fn decodes_by_game_mode_key() {
+        let body = [5];
+        for (is_ranked, is_custom) in [(false, false), (true, false), (false, true)] {
+            let event = ModeEvent::decode(
+                key(GameModeType::SuddenDeath, is_ranked, is_custom),
+                Role::Client,
+                NetworkEvent::RemoteEnemySpotted,
+                &body,
+            )
+            .unwrap();
+            assert!(matches!(
+                event,
+                ModeEvent::Elimination(EliminationEvent::RemoteEnemySpotted(PlayerId {
+                    player: 5
+                }))
+            ));
+        }
+        assert_eq!(<SuddenDeath as GameMode>::TYPE, GameModeType::SuddenDeath);
+    }
+
+    #[test]
+    fn decode_rejects_unregistered_and_misdirected_events() {
+        let body = [5];
+        assert!(matches!(
+            ModeEvent::decode(
+                key(GameModeType::TestMode, false, false),
+                Role::Client,
+                NetworkEvent::RemoteEnemySpotted,
+                &body,
+            ),
+            Err(EventError::Unregistered(NetworkEvent::RemoteEnemySpotted))
+        ));
+        assert!(matches!(
+            ModeEvent::decode(
+                key(GameModeType::Campaign, false, false),
+                Role::Server,
+                NetworkEvent::MakeInvisible,
+                &body,
+            ),
+            Err(EventError::WrongDirection(NetworkEvent::MakeInvisible))
+        ));
+    }
+}
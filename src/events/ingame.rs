@@ -2,6 +2,7 @@ use byteserde::prelude::{ByteDeserializeSlice, ByteSerializeHeap};
 use byteserde_derive::{ByteDeserializeSlice, ByteSerializeHeap};
 
 use crate::{
+    event_code::NetworkEvent,
     types::{
         BinaryWriterString, Byte3, ByteFloat, CompressedVec3, CubeStatus, DVec3, GameEndReason,
         HitCubeInfo, IngameStatId, ItemDescriptor, PingType, PosQuatPair, SQuat, SVec3, TargetType,
@@ -24,6 +25,46 @@ pub struct UpdateGameStats {
     pub delta_score: u32,
 }
 
+/// A client claiming a bonus for a kill, an assist or healing an ally.
+#[derive(Debug, Clone, Copy, ByteDeserializeSlice, ByteSerializeHeap)]
+pub struct PlayerBonus {
+    pub player_id: u8,
+    pub target_player_id: u8,
+}
+
+/// A client claiming a bonus for cubes it destroyed or healed.
+#[derive(Debug, Clone, Copy, ByteDeserializeSlice, ByteSerializeHeap)]
+pub struct CubesBonus {
+    pub player_id: u8,
+    pub cubes: u32,
+}
+
+/// Points the server granted, before they show up in [`UpdateGameStats`].
+#[derive(Debug, Clone, Copy, ByteDeserializeSlice, ByteSerializeHeap)]
+pub struct BonusAwarded {
+    pub player_id: u8,
+    pub stat_id: IngameStatId,
+    pub amount: u32,
+    pub score: u32,
+}
+
+/// The stat a bonus request counts towards.
+pub fn bonus_stat(code: NetworkEvent) -> Option<IngameStatId> {
+    Some(match code {
+        NetworkEvent::KillBonusRequest => IngameStatId::Kill,
+        NetworkEvent::AssistBonusRequest => IngameStatId::KillAssist,
+        NetworkEvent::HeallingAssistBonusRequest => IngameStatId::HealAssist,
+        NetworkEvent::ProtectTeamMateBonusRequest => IngameStatId::DestroyedCubesInProtection,
+        NetworkEvent::DefendTheBaseBonusRequest => IngameStatId::DestroyedCubesDefendingTheBase,
+        NetworkEvent::DestroyCubesBonusRequest => IngameStatId::DestroyedCubes,
+        NetworkEvent::HealCubesBonusRequest => IngameStatId::HealCubes,
+        NetworkEvent::AwardTeamBaseProtoniumDestroyedRequest => {
+            IngameStatId::DestroyedProtoniumCubes
+        }
+        _ => return None,
+    })
+}
+
 #[derive(Debug, Clone, ByteDeserializeSlice, ByteSerializeHeap)]
 pub struct UpdateVotingAfterBattle {
     pub player_name: BinaryWriterString,
@@ -127,6 +168,50 @@ pub struct DestroyCubeNoEffect {
     pub hit_cubes: Vec<CubeStatus>,
 }
 
+/// A client's report of its shot hitting cubes, which the server turns into
+/// [`DestroyCubesFull`].
+#[derive(Debug, Clone, ByteDeserializeSlice, ByteSerializeHeap)]
+pub struct DamageCube {
+    pub shooting_machine_id: i16,
+    pub hit_machine_id: i16,
+    pub item_category: i16,
+    pub item_size: i16,
+    pub stack_count: u8,
+    pub target_type: TargetType,
+    pub hit_effect_offset: CompressedVec3<768>,
+    pub hit_effect_normal: CompressedVec3<255>,
+    #[byteserde(replace(hit_cubes.len()))]
+    pub num_hit_cubes: u16,
+    #[byteserde(deplete(usize::from(num_hit_cubes)))]
+    pub hit_cubes: Vec<HitCubeInfo>,
+    pub timestamp: f32,
+}
+
+/// Answered with [`DestroyCubeEffectOnly`].
+#[derive(Debug, Clone, Copy, ByteDeserializeSlice, ByteSerializeHeap)]
+pub struct DamageCubeEffectOnly {
+    pub shooting_machine_id: i16,
+    pub hit_machine_id: i16,
+    pub item_category: i16,
+    pub stack_count: u8,
+    pub target_type: TargetType,
+    pub hit_effect_offset: CompressedVec3<768>,
+    pub hit_effect_normal: CompressedVec3<255>,
+    pub hit_cube: HitCubeInfo,
+}
+
+/// Answered with [`DestroyCubeNoEffect`].
+#[derive(Debug, Clone, ByteDeserializeSlice, ByteSerializeHeap)]
+pub struct DamageCubeNoEffect {
+    pub shooting_machine_id: i16,
+    pub hit_machine_id: i16,
+    pub target_type: TargetType,
+    #[byteserde(replace(hit_cubes.len()))]
+    pub num_hits: u16,
+    #[byteserde(deplete(usize::from(num_hits)))]
+    pub hit_cubes: Vec<HitCubeInfo>,
+}
+
 #[derive(Debug, Clone, Copy, ByteDeserializeSlice, ByteSerializeHeap)]
 pub struct WeaponFireEffect {
     pub launch_position: CompressedVec3<768>,
@@ -214,6 +299,19 @@ pub struct ShieldModuleEvent {
     pub pos: PosQuatPair,
     pub firing_player_id: u8,
 }
+/// A shield placed by another player, as relayed by the server.
+#[derive(Debug, Clone, Copy, ByteDeserializeSlice, ByteSerializeHeap)]
+pub struct ShieldSpawned {
+    pub pos: PosQuatPair,
+    pub firing_player_id: u8,
+    pub owner_machine_id: i16,
+}
+#[derive(Debug, Clone, Copy, ByteDeserializeSlice, ByteSerializeHeap)]
+pub struct OpenShield {
+    pub owner_machine_id: i16,
+    //bool
+    pub is_open: u8,
+}
 #[derive(Debug, Clone, Copy, ByteDeserializeSlice, ByteSerializeHeap)]
 pub struct TeleportActivateEffect {
     //bool
@@ -323,3 +421,163 @@ pub struct TeamBaseState {
     pub current_progress: ByteFloat<4>,
     pub max_progress: ByteFloat<4>,
 }
+
+#[cfg(test)]
+mod tests {
+    use byteserde::des_slice::from_slice;
+
+    use super::*;
+    use crate::types::tests::to_bytes;
+
+    // position x, y, z then rotation x, y, z, each an i16
+    const POS_QUAT: [u8; 12] = [1, 0, 2, 0, 3, 0, 4, 0, 5, 0, 6, 0];
+
+    #[test]
+    fn shield_module_event_layout() {
+        let bytes = [&POS_QUAT[..], &[7]].concat();
+        let event = from_slice::<ShieldModuleEvent>(&bytes).unwrap();
+        assert_eq!(event.firing_player_id, 7);
+        assert_eq!(to_bytes(&event), bytes);
+    }
+
+    #[test]
+    fn shield_spawned_layout() {
+        let bytes = [&POS_QUAT[..], &[7, 0x34, 0x12]].concat();
+        let event = from_slice::<ShieldSpawned>(&bytes).unwrap();
+        assert_eq!(event.firing_player_id, 7);
+        assert_eq!(event.owner_machine_id, 0x1234);
+        assert_eq!(to_bytes(&event), bytes);
+    }
+
+    #[test]
+    fn open_shield_layout() {
+        let event = OpenShield {
+            owner_machine_id: -2,
+            is_open: 1,
+        };
+        assert_eq!(to_bytes(&event), [0xfe, 0xff, 1]);
+        let event = from_slice::<OpenShield>(&[5, 0, 0]).unwrap();
+        assert_eq!((event.owner_machine_id, event.is_open), (5, 0));
+    }
+
+    // shooting machine, hit machine
+    const MACHINES: [u8; 4] = [1, 0, 2, 0];
+    // hit effect offset then normal, each three i16s
+    const HIT_EFFECT: [u8; 12] = [6, 0, 7, 0, 8, 0, 9, 0, 10, 0, 11, 0];
+    // position then damage
+    const HIT_CUBE: [u8; 7] = [1, 2, 3, 0x10, 0, 0, 0];
+
+    fn assert_hit_cube(cube: &HitCubeInfo, pos: (u8, u8, u8), damage: i32) {
+        assert_eq!((cube.pos.x, cube.pos.y, cube.pos.z), pos);
+        assert_eq!(cube.damage, damage);
+    }
+
+    #[test]
+    fn damage_cube_layout() {
+        let bytes = [
+            &MACHINES[..],
+            // item category, item size, stack count, target type
+            &[3, 0, 4, 0, 5, 0],
+            &HIT_EFFECT,
+            &[2, 0],
+            &HIT_CUBE,
+            &[4, 5, 6, 0xff, 0xff, 0xff, 0xff],
+            &1.5f32.to_le_bytes(),
+        ]
+        .concat();
+        let event = from_slice::<DamageCube>(&bytes).unwrap();
+        assert_eq!((event.shooting_machine_id, event.hit_machine_id), (1, 2));
+        assert_eq!((event.item_category, event.item_size), (3, 4));
+        assert_eq!(event.stack_count, 5);
+        assert_eq!(event.target_type, TargetType::Player);
+        let offset = event.hit_effect_offset;
+        assert_eq!((offset.x, offset.y, offset.z), (6, 7, 8));
+        assert_eq!(event.num_hit_cubes, 2);
+        assert_hit_cube(&event.hit_cubes[0], (1, 2, 3), 0x10);
+        assert_hit_cube(&event.hit_cubes[1], (4, 5, 6), -1);
+        assert_eq!(event.timestamp, 1.5);
+        assert_eq!(to_bytes(&event), bytes);
+    }
+
+    #[test]
+    fn damage_cube_effect_only_layout() {
+        let bytes = [
+            &MACHINES[..],
+            // item category, stack count, target type
+            &[3, 0, 5, 2],
+            &HIT_EFFECT,
+            &HIT_CUBE,
+        ]
+        .concat();
+        let event = from_slice::<DamageCubeEffectOnly>(&bytes).unwrap();
+        assert_eq!((event.shooting_machine_id, event.hit_machine_id), (1, 2));
+        assert_eq!((event.item_category, event.stack_count), (3, 5));
+        assert_eq!(event.target_type, TargetType::TeamBase);
+        let normal = event.hit_effect_normal;
+        assert_eq!((normal.x, normal.y, normal.z), (9, 10, 11));
+        assert_hit_cube(&event.hit_cube, (1, 2, 3), 0x10);
+        assert_eq!(to_bytes(&event), bytes);
+    }
+
+    #[test]
+    fn damage_cube_no_effect_layout() {
+        let bytes = [&MACHINES[..], &[1, 1, 0], &HIT_CUBE].concat();
+        let event = from_slice::<DamageCubeNoEffect>(&bytes).unwrap();
+        assert_eq!((event.shooting_machine_id, event.hit_machine_id), (1, 2));
+        assert_eq!(event.target_type, TargetType::Environment);
+        assert_eq!(event.num_hits, 1);
+        assert_hit_cube(&event.hit_cubes[0], (1, 2, 3), 0x10);
+        assert_eq!(to_bytes(&event), bytes);
+    }
+
+    #[test]
+    fn bonus_layouts() {
+        let event = from_slice::<PlayerBonus>(&[3, 4]).unwrap();
+        assert_eq!((event.player_id, event.target_player_id), (3, 4));
+        assert_eq!(to_bytes(&event), [3, 4]);
+
+        let bytes = [3, 0x2c, 1, 0, 0];
+        let event = from_slice::<CubesBonus>(&bytes).unwrap();
+        assert_eq!((event.player_id, event.cubes), (3, 300));
+        assert_eq!(to_bytes(&event), bytes);
+
+        let bytes = [3, 4, 1, 0, 0, 0, 100, 0, 0, 0];
+        let event = from_slice::<BonusAwarded>(&bytes).unwrap();
+        assert_eq!(event.player_id, 3);
+        assert_eq!(event.stat_id, IngameStatId::Kill);
+        assert_eq!((event.amount, event.score), (1, 100));
+        assert_eq!(to_bytes(&event), bytes);
+    }
+
+    #[test]
+    fn bonus_requests_count_towards_their_stat() {
+        for (code, stat) in [
+            (NetworkEvent::KillBonusRequest, IngameStatId::Kill),
+            (NetworkEvent::AssistBonusRequest, IngameStatId::KillAssist),
+            (
+                NetworkEvent::HeallingAssistBonusRequest,
+                IngameStatId::HealAssist,
+            ),
+            (
+                NetworkEvent::ProtectTeamMateBonusRequest,
+                IngameStatId::DestroyedCubesInProtection,
+            ),
+            (
+                NetworkEvent::DefendTheBaseBonusRequest,
+                IngameStatId::DestroyedCubesDefendingTheBase,
+            ),
+            (
+                NetworkEvent::DestroyCubesBonusRequest,
+                IngameStatId::DestroyedCubes,
+            ),
+            (NetworkEvent::HealCubesBonusRequest, IngameStatId::HealCubes),
+            (
+                NetworkEvent::AwardTeamBaseProtoniumDestroyedRequest,
+                IngameStatId::DestroyedProtoniumCubes,
+            ),
+        ] {
+            assert_eq!(bonus_stat(code), Some(stat), "{code:?}");
+        }
+        assert_eq!(bonus_stat(NetworkEvent::ConfirmedKill), None);
+    }
+}
@@ -31,4 +31,9 @@ pub struct LoadingProgress {
     pub progress: f32,
 }
 
+#[derive(Debug, Clone, ByteDeserializeSlice, ByteSerializeHeap)]
+pub struct GameGuid {
+    pub guid: BinaryWriterString,
+}
+
 // EacMessage/EacMessageDependency probably unneeded, skipped
@@ -1,8 +1,96 @@
-pub struct NetServer {}
+use std::fmt;
 
+use crate::event_code::NetworkEvent;
+
+#[cfg(feature = "tokio")]
+pub mod async_server;
+mod channel;
+pub mod client;
+pub mod discovery;
+mod fragment;
+pub mod guid;
+pub mod message;
+pub mod packet;
+pub mod peer;
+pub mod rate_limit;
+pub mod server;
+pub mod session;
+pub mod simulator;
+pub mod stats;
+pub mod transport;
+
+pub use client::NetClient;
+pub use discovery::{
+    DiscoveryResponse, ServerInfo, parse_discovery_response, send_discovery_request,
+};
+pub use guid::GameGuidValidator;
+pub use message::{EventError, decode_event, encode_event, send};
+pub use peer::{DisconnectReason, NetPeer, PeerId};
+pub use rate_limit::{LimitAction, RateLimit, RateLimits};
+pub use server::{ConnectionRequest, NetServer, Recipients};
+pub use session::{Join, PlayerSession, ReconnectToken, SessionRegistry};
+pub use simulator::{NetworkConditions, SimulatedTransport};
+pub use stats::{ChannelStats, EventStats, PeerStats};
+pub use transport::{MemoryNetwork, MemoryTransport, Transport, UdpTransport};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum SendOptions {
     Unreliable,
     ReliableUnordered,
     Sequenced,
     ReliableOrdered,
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendError {
+    NotConnected,
+    QueueFull,
+    TooLarge,
+}
+
+impl fmt::Display for SendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendError::NotConnected => write!(f, "peer is not connected"),
+            SendError::QueueFull => write!(f, "send queue is full"),
+            SendError::TooLarge => write!(f, "payload is too large for its delivery method"),
+        }
+    }
+}
+
+impl std::error::Error for SendError {}
+
+/// Callbacks raised while polling a [`NetServer`] or [`NetClient`].
+pub trait NetEventListener {
+    /// Decides whether to accept a new connection. Rejected clients are sent
+    /// a disconnect packet.
+    fn on_connection_request(&mut self, _request: &ConnectionRequest) -> bool {
+        true
+    }
+    /// Picks who a new player plays as, when the server tracks sessions with
+    /// [`NetServer::set_sessions`]. Returning `None` leaves the peer out of
+    /// the registry, so it can't reconnect.
+    fn on_new_session(&mut self, _peer: &NetPeer) -> Option<PlayerSession> {
+        None
+    }
+    fn on_peer_connected(&mut self, _peer: &mut NetPeer) {}
+    fn on_peer_disconnected(&mut self, _peer: &NetPeer, _reason: DisconnectReason) {}
+    /// Receives every payload. By default, payloads are decoded as event
+    /// messages and passed on to [`NetEventListener::on_event`]; ones that
+    /// aren't, or that this end isn't meant to receive, are dropped.
+    fn on_receive(&mut self, peer: &mut NetPeer, payload: &[u8], options: SendOptions) {
+        if let Ok((code, body)) = decode_event(payload)
+            && code.accepted_by(peer.role())
+        {
+            self.on_event(peer, code, body, options);
+        }
+    }
+    fn on_event(
+        &mut self,
+        _peer: &mut NetPeer,
+        _code: NetworkEvent,
+        _body: &[u8],
+        _options: SendOptions,
+    ) {
+    }
+}
@@ -0,0 +1,585 @@
+//! Async access to a [`NetServer`], driven by a background tokio task.
+//!
+//! [`bind`] or [`serve`] spawns the task and returns a cloneable
+//! [`ServerHandle`] for sending and a [`NetEvents`] stream of what the server
+//! receives.
+
+use std::{
+    collections::{HashSet, VecDeque},
+    io,
+    net::SocketAddr,
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use byteserde::prelude::ByteSerializeHeap;
+use futures_core::Stream;
+use tokio::{
+    net::{ToSocketAddrs, UdpSocket},
+    sync::{
+        mpsc::{self, error::TrySendError},
+        oneshot,
+    },
+    time::MissedTickBehavior,
+};
+
+use crate::{
+    event_code::NetworkEvent,
+    events::Role,
+    net::{
+        DisconnectReason, EventError, NetEventListener, NetPeer, NetServer, PeerId, PeerStats,
+        Recipients, SendError, SendOptions, encode_event, transport::Transport,
+    },
+    photon::enter_battle::NetworkConfig,
+};
+
+impl Transport for UdpSocket {
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        UdpSocket::local_addr(self)
+    }
+    fn send_to(&mut self, datagram: &[u8], addr: SocketAddr) -> io::Result<()> {
+        self.try_send_to(datagram, addr).map(|_| ())
+    }
+    fn recv_from(&mut self, buf: &mut [u8]) -> io::Result<Option<(usize, SocketAddr)>> {
+        loop {
+            match self.try_recv_from(buf) {
+                Ok(received) => return Ok(Some(received)),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+                Err(e) if e.kind() == io::ErrorKind::ConnectionReset => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetEvent {
+    Connected {
+        peer: PeerId,
+        addr: SocketAddr,
+    },
+    Disconnected {
+        peer: PeerId,
+        reason: DisconnectReason,
+    },
+    Received {
+        peer: PeerId,
+        code: NetworkEvent,
+        body: Vec<u8>,
+        options: SendOptions,
+    },
+}
+
+#[derive(Debug)]
+struct PendingSend {
+    peer: PeerId,
+    payload: Vec<u8>,
+    options: SendOptions,
+    reply: oneshot::Sender<Result<(), SendError>>,
+}
+
+#[derive(Debug)]
+struct PendingBroadcast {
+    recipients: Recipients<'static>,
+    payload: Vec<u8>,
+    options: SendOptions,
+    reply: oneshot::Sender<Vec<(PeerId, SendError)>>,
+}
+
+#[derive(Debug)]
+enum Command {
+    Send(PendingSend),
+    Broadcast(PendingBroadcast),
+    Disconnect(PeerId),
+    PeerStats(PeerId, oneshot::Sender<Option<PeerStats>>),
+    Stats(oneshot::Sender<Vec<PeerStats>>),
+}
+
+/// Sends through the server task. Cheap to clone; the server shuts down once
+/// every handle has been dropped.
+#[derive(Debug, Clone)]
+pub struct ServerHandle {
+    commands: mpsc::Sender<Command>,
+    local_addr: SocketAddr,
+}
+
+impl ServerHandle {
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+    /// Queues `payload` for `peer`. When the reliable channel already holds
+    /// `max_sent_message_queue_size` packets, this waits until the peer has
+    /// acknowledged enough of them to make room. Once that many sends are
+    /// waiting, further commands wait to be taken too.
+    pub async fn send(
+        &self,
+        peer: PeerId,
+        payload: Vec<u8>,
+        options: SendOptions,
+    ) -> Result<(), SendError> {
+        let (reply, accepted) = oneshot::channel();
+        let send = PendingSend {
+            peer,
+            payload,
+            options,
+            reply,
+        };
+        self.commands
+            .send(Command::Send(send))
+            .await
+            .map_err(|_| SendError::NotConnected)?;
+        accepted.await.unwrap_or(Err(SendError::NotConnected))
+    }
+    pub async fn send_event(
+        &self,
+        peer: PeerId,
+        code: NetworkEvent,
+        data: &impl ByteSerializeHeap,
+        options: SendOptions,
+    ) -> Result<(), EventError> {
+        if !code.accepted_by(Role::Client) {
+            return Err(EventError::WrongDirection(code));
+        }
+        let payload = encode_event(code, data)?;
+        Ok(self.send(peer, payload, options).await?)
+    }
+    /// Sends `payload` to every connected peer `recipients` includes,
+    /// returning the peers it couldn't be queued for. Unlike
+    /// [`ServerHandle::send`] this never waits for room: a peer whose channel
+    /// is full, or who still has a send waiting on it, is returned with
+    /// [`SendError::QueueFull`].
+    pub async fn broadcast(
+        &self,
+        recipients: Recipients<'static>,
+        payload: Vec<u8>,
+        options: SendOptions,
+    ) -> Vec<(PeerId, SendError)> {
+        let broadcast = |reply| {
+            Command::Broadcast(PendingBroadcast {
+                recipients,
+                payload,
+                options,
+                reply,
+            })
+        };
+        // Nobody is left to send to once the server has stopped.
+        self.request(broadcast).await.unwrap_or_default()
+    }
+    pub async fn broadcast_event(
+        &self,
+        recipients: Recipients<'static>,
+        code: NetworkEvent,
+        data: &impl ByteSerializeHeap,
+        options: SendOptions,
+    ) -> Result<Vec<(PeerId, SendError)>, EventError> {
+        if !code.accepted_by(Role::Client) {
+            return Err(EventError::WrongDirection(code));
+        }
+        let payload = encode_event(code, data)?;
+        Ok(self.broadcast(recipients, payload, options).await)
+    }
+    pub async fn disconnect(&self, peer: PeerId) {
+        // Nothing to disconnect from once the server has stopped.
+        let _ = self.commands.send(Command::Disconnect(peer)).await;
+    }
+    pub async fn peer_stats(&self, peer: PeerId) -> Option<PeerStats> {
+        self.request(|reply| Command::PeerStats(peer, reply))
+            .await
+            .flatten()
+    }
+    /// Traffic snapshots for every connected peer.
+    pub async fn stats(&self) -> Vec<PeerStats> {
+        self.request(Command::Stats).await.unwrap_or_default()
+    }
+    /// Sends the command `command` builds and waits for its answer, or
+    /// `None` if the server has stopped.
+    async fn request<R>(&self, command: impl FnOnce(oneshot::Sender<R>) -> Command) -> Option<R> {
+        let (reply, answer) = oneshot::channel();
+        self.commands.send(command(reply)).await.ok()?;
+        answer.await.ok()
+    }
+}
+
+/// Everything the server receives, in order.
+#[derive(Debug)]
+pub struct NetEvents {
+    events: mpsc::Receiver<NetEvent>,
+}
+
+impl NetEvents {
+    /// The next event, or `None` once the server has stopped.
+    pub async fn recv(&mut self) -> Option<NetEvent> {
+        self.events.recv().await
+    }
+}
+
+impl Stream for NetEvents {
+    type Item = NetEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<NetEvent>> {
+        self.events.poll_recv(cx)
+    }
+}
+
+/// Binds a UDP server and runs it on a spawned task. Must be called from
+/// within a tokio runtime.
+pub async fn bind(
+    addr: impl ToSocketAddrs,
+    config: NetworkConfig,
+) -> io::Result<(ServerHandle, NetEvents)> {
+    let socket = UdpSocket::bind(addr).await?;
+    serve(NetServer::new(socket, config))
+}
+
+/// Runs an already set up server on a spawned task, keeping its rate limits,
+/// discovery info, GUID validation and sessions. Must be called from within
+/// a tokio runtime.
+pub fn serve(server: NetServer<UdpSocket>) -> io::Result<(ServerHandle, NetEvents)> {
+    let local_addr = server.local_addr()?;
+    let capacity = usize::from(server.config().max_sent_message_queue_size).max(1);
+    let (commands, command_rx) = mpsc::channel(capacity);
+    let (event_tx, events) = mpsc::channel(capacity);
+    tokio::spawn(drive(server, capacity, command_rx, event_tx));
+    Ok((
+        ServerHandle {
+            commands,
+            local_addr,
+        },
+        NetEvents { events },
+    ))
+}
+
+/// Collects what a poll raises. Events stay here until the [`NetEvents`]
+/// stream has room. Once `max_sent_message_queue_size` of them are waiting
+/// the server stops reading its socket, so a slow consumer holds up its
+/// peers rather than growing this without bound. A single poll can still
+/// overshoot the limit by whatever one batch of datagrams raises, and peers
+/// hear nothing from the server while it waits, so a consumer stalled for
+/// longer than the disconnect timeout loses its clients.
+#[derive(Default)]
+struct Collector {
+    events: VecDeque<NetEvent>,
+}
+
+impl NetEventListener for Collector {
+    fn on_peer_connected(&mut self, peer: &mut NetPeer) {
+        self.events.push_back(NetEvent::Connected {
+            peer: peer.id(),
+            addr: peer.addr(),
+        });
+    }
+    fn on_peer_disconnected(&mut self, peer: &NetPeer, reason: DisconnectReason) {
+        self.events.push_back(NetEvent::Disconnected {
+            peer: peer.id(),
+            reason,
+        });
+    }
+    fn on_event(
+        &mut self,
+        peer: &mut NetPeer,
+        code: NetworkEvent,
+        body: &[u8],
+        options: SendOptions,
+    ) {
+        self.events.push_back(NetEvent::Received {
+            peer: peer.id(),
+            code,
+            body: body.to_vec(),
+            options,
+        });
+    }
+}
+
+enum Wake {
+    Readable(io::Result<()>),
+    Tick,
+    Command(Option<Command>),
+    Room,
+}
+
+async fn drive(
+    mut server: NetServer<UdpSocket>,
+    capacity: usize,
+    mut commands: mpsc::Receiver<Command>,
+    events: mpsc::Sender<NetEvent>,
+) {
+    let update = Duration::from_millis(server.config().min_update_timeout.max(1).into());
+    let mut tick = tokio::time::interval(update);
+    tick.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut collector = Collector::default();
+    // Sends waiting for room in a full reliable channel, oldest first.
+    let mut blocked = VecDeque::new();
+    loop {
+        let backed_up = collector.events.len() >= capacity;
+        let wake = tokio::select! {
+            readable = server.transport().readable(), if !backed_up => Wake::Readable(readable),
+            _ = tick.tick(), if !backed_up => Wake::Tick,
+            command = commands.recv(), if blocked.len() < capacity => Wake::Command(command),
+            _ = events.reserve(), if !collector.events.is_empty() => Wake::Room,
+        };
+        match wake {
+            Wake::Readable(Err(_)) => break,
+            Wake::Readable(Ok(())) | Wake::Tick | Wake::Room => {}
+            Wake::Command(None) => {
+                server.shutdown(&mut collector);
+                break;
+            }
+            Wake::Command(Some(Command::Send(send))) => {
+                let waiting = blocked
+                    .iter()
+                    .any(|b: &PendingSend| (b.peer, b.options) == (send.peer, send.options));
+                // Anything behind a blocked send has to wait its turn too, or
+                // it would overtake it.
+                if waiting {
+                    blocked.push_back(send);
+                } else if let Some(send) = try_send(&mut server, send) {
+                    blocked.push_back(send);
+                }
+            }
+            Wake::Command(Some(Command::Broadcast(broadcast))) => {
+                let failed = try_broadcast(&mut server, &blocked, &broadcast);
+                let _ = broadcast.reply.send(failed);
+            }
+            Wake::Command(Some(Command::Disconnect(peer))) => server.disconnect(peer),
+            Wake::Command(Some(Command::PeerStats(peer, reply))) => {
+                let _ = reply.send(server.peer_stats(peer));
+            }
+            Wake::Command(Some(Command::Stats(reply))) => {
+                let _ = reply.send(server.stats());
+            }
+        }
+        forward(&mut collector.events, &events);
+        if collector.events.len() < capacity
+            && server.poll_at(Instant::now(), &mut collector).is_err()
+        {
+            break;
+        }
+        let mut stuck = HashSet::new();
+        for send in std::mem::take(&mut blocked) {
+            if stuck.contains(&(send.peer, send.options)) {
+                blocked.push_back(send);
+            } else if let Some(send) = try_send(&mut server, send) {
+                stuck.insert((send.peer, send.options));
+                blocked.push_back(send);
+            }
+        }
+        forward(&mut collector.events, &events);
+    }
+    for event in collector.events.drain(..) {
+        let _ = events.send(event).await;
+    }
+}
+
+/// Passes on as many events as the stream has room for.
+fn forward(pending: &mut VecDeque<NetEvent>, events: &mpsc::Sender<NetEvent>) {
+    while let Some(event) = pending.pop_front() {
+        match events.try_send(event) {
+            Ok(()) => {}
+            Err(TrySendError::Full(event)) => {
+                pending.push_front(event);
+                break;
+            }
+            // A dropped event stream just means nobody is listening anymore.
+            Err(TrySendError::Closed(_)) => pending.clear(),
+        }
+    }
+}
+
+/// Broadcasts past peers with a send still waiting on the same channel, so
+/// the broadcast can't overtake it.
+fn try_broadcast(
+    server: &mut NetServer<UdpSocket>,
+    blocked: &VecDeque<PendingSend>,
+    broadcast: &PendingBroadcast,
+) -> Vec<(PeerId, SendError)> {
+    let waiting: HashSet<PeerId> = blocked
+        .iter()
+        .filter(|send| send.options == broadcast.options)
+        .map(|send| send.peer)
+        .collect();
+    let recipients = broadcast.recipients;
+    let ready = |peer: &NetPeer| recipients.includes(peer) && !waiting.contains(&peer.id());
+    let mut failed = server.broadcast(
+        Recipients::Matching(&ready),
+        &broadcast.payload,
+        broadcast.options,
+    );
+    failed.extend(
+        waiting
+            .iter()
+            .filter(|&&peer| {
+                server
+                    .peer(peer)
+                    .is_some_and(|peer| peer.is_connected() && recipients.includes(peer))
+            })
+            .map(|&peer| (peer, SendError::QueueFull)),
+    );
+    failed
+}
+
+/// Hands `send` back if the channel is still full.
+fn try_send(server: &mut NetServer<UdpSocket>, send: PendingSend) -> Option<PendingSend> {
+    match server.send(send.peer, &send.payload, send.options) {
+        Err(SendError::QueueFull) => Some(send),
+        result => {
+            let _ = send.reply.send(result);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        events::ingame::PlayerId,
+        net::{
+            NetClient,
+            peer::{PeerSettings, tests::config},
+        },
+    };
+
+    #[derive(Default)]
+    struct Received(Vec<(NetworkEvent, Vec<u8>)>);
+
+    impl NetEventListener for Received {
+        fn on_event(
+            &mut self,
+            _peer: &mut NetPeer,
+            code: NetworkEvent,
+            body: &[u8],
+            _options: SendOptions,
+        ) {
+            self.0.push((code, body.to_vec()));
+        }
+    }
+
+    /// Polls `client` until `done`, giving the server task room to run.
+    async fn poll_until(
+        client: &mut NetClient,
+        received: &mut Received,
+        mut done: impl FnMut(&NetClient, &Received) -> bool,
+    ) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !done(client, received) {
+            assert!(Instant::now() < deadline, "timed out");
+            client.poll(received).unwrap();
+            tokio::time::sleep(Duration::from_millis(2)).await;
+        }
+    }
+
+    fn player(player: u8) -> PlayerId {
+        PlayerId { player }
+    }
+
+    #[tokio::test]
+    async fn round_trip() {
+        let (server, mut events) = bind("127.0.0.1:0", config()).await.unwrap();
+        let mut client = NetClient::connect(server.local_addr(), config(), b"").unwrap();
+        let mut received = Received::default();
+        poll_until(&mut client, &mut received, |client, _| {
+            client.is_connected()
+        })
+        .await;
+        client
+            .send_event(
+                NetworkEvent::OnPlayerInputChanged,
+                &player(7),
+                SendOptions::ReliableOrdered,
+            )
+            .unwrap();
+        client.poll(&mut received).unwrap();
+
+        let Some(NetEvent::Connected { peer, addr }) = events.recv().await else {
+            panic!("expected a connection");
+        };
+        assert_eq!(addr.port(), client.local_addr().unwrap().port());
+        assert_eq!(
+            events.recv().await,
+            Some(NetEvent::Received {
+                peer,
+                code: NetworkEvent::OnPlayerInputChanged,
+                body: vec![7],
+                options: SendOptions::ReliableOrdered,
+            })
+        );
+
+        let reply = NetworkEvent::OnServerReceivedInputChange;
+        server
+            .send_event(peer, reply, &player(8), SendOptions::ReliableOrdered)
+            .await
+            .unwrap();
+        let failed = server
+            .broadcast_event(
+                Recipients::All,
+                reply,
+                &player(9),
+                SendOptions::ReliableOrdered,
+            )
+            .await
+            .unwrap();
+        assert!(failed.is_empty());
+        poll_until(&mut client, &mut received, |_, received| {
+            received.0.len() == 2
+        })
+        .await;
+        assert_eq!(received.0, [(reply, vec![8]), (reply, vec![9])]);
+
+        let stats = server.peer_stats(peer).await.unwrap();
+        assert_eq!(stats.events[&reply].messages_sent, 2);
+        assert_eq!(
+            stats.events[&NetworkEvent::OnPlayerInputChanged].messages_received,
+            1
+        );
+        assert_eq!(server.stats().await, [stats]);
+    }
+
+    #[tokio::test]
+    async fn send_waits_for_room() {
+        let mut config = config();
+        config.max_sent_message_queue_size = 2;
+        config.is_acks_long = false;
+        let (server, mut events) = bind("127.0.0.1:0", config.clone()).await.unwrap();
+        let mut client = NetClient::connect(server.local_addr(), config.clone(), b"").unwrap();
+        let mut received = Received::default();
+        poll_until(&mut client, &mut received, |client, _| {
+            client.is_connected()
+        })
+        .await;
+        let Some(NetEvent::Connected { peer, .. }) = events.recv().await else {
+            panic!("expected a connection");
+        };
+
+        // Without the client acking, sends fill the send window and then the
+        // channel's queue. Two more wait for room and the rest fill the
+        // command channel.
+        let queued = usize::from(PeerSettings::new(&config, Role::Server).window_size) + 2;
+        let count = queued as u8 + 6;
+        let code = NetworkEvent::OnServerReceivedInputChange;
+        let sends: Vec<_> = (0..count)
+            .map(|i| {
+                let server = server.clone();
+                tokio::spawn(async move {
+                    server
+                        .send_event(peer, code, &player(i), SendOptions::ReliableOrdered)
+                        .await
+                })
+            })
+            .collect();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(server.commands.capacity(), 0);
+        let done = sends.iter().filter(|send| send.is_finished()).count();
+        assert_eq!(done, queued);
+
+        poll_until(&mut client, &mut received, |_, received| {
+            received.0.len() == count.into()
+        })
+        .await;
+        for send in sends {
+            send.await.unwrap().unwrap();
+        }
+        let expected: Vec<_> = (0..count).map(|i| (code, vec![i])).collect();
+        assert_eq!(received.0, expected);
+    }
+}
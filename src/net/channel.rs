@@ -0,0 +1,531 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crate::net::{
+    SendError, SendOptions,
+    packet::{
+        FragmentHeader, MAX_SEQUENCE, Packet, PacketHeader, PacketProperty, channel_id,
+        relative_sequence,
+    },
+    peer::OutgoingQueue,
+};
+
+const BITS_IN_BYTE: u16 = 8;
+
+fn next_sequence(seq: u16) -> u16 {
+    (seq + 1) % MAX_SEQUENCE
+}
+
+/// A payload as carried by a single channeled packet, which may be one part
+/// of a larger fragmented message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ChannelPacket {
+    pub(crate) fragment: Option<FragmentHeader>,
+    pub(crate) payload: Vec<u8>,
+}
+
+impl ChannelPacket {
+    fn header(&self, options: SendOptions, sequence: u16) -> PacketHeader {
+        PacketHeader {
+            fragment: self.fragment,
+            ..PacketHeader::channeled(options, sequence)
+        }
+    }
+}
+
+impl From<Packet<'_>> for ChannelPacket {
+    fn from(packet: Packet<'_>) -> Self {
+        Self {
+            fragment: packet.header.fragment,
+            payload: packet.payload.to_vec(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct PendingPacket {
+    packet: ChannelPacket,
+    last_sent: Option<Instant>,
+}
+
+/// Sliding window reliable delivery, modeled on LiteNetLib's `ReliableChannel`.
+///
+/// Every packet gets a sequence number and stays in the send window until the
+/// remote acknowledges it; the receive side keeps a bitfield of the sequences
+/// it has seen in its own window and sends it back as an ack packet.
+#[derive(Debug)]
+pub(crate) struct ReliableChannel {
+    options: SendOptions,
+    window_size: u16,
+    max_queue_size: usize,
+
+    queue: VecDeque<ChannelPacket>,
+    pending: Vec<Option<PendingPacket>>,
+    local_sequence: u16,
+    local_window_start: u16,
+
+    remote_sequence: u16,
+    remote_window_start: u16,
+    acks: Vec<u8>,
+    must_send_acks: bool,
+    // Ordered channels hold packets that arrived early until the gap fills;
+    // unordered channels deliver them immediately and only remember them.
+    held: Vec<Option<ChannelPacket>>,
+    early_received: Vec<bool>,
+
+    packets_sent: u64,
+    resends: u64,
+}
+
+impl ReliableChannel {
+    pub(crate) fn new(options: SendOptions, window_size: u16, max_queue_size: usize) -> Self {
+        debug_assert!(matches!(
+            options,
+            SendOptions::ReliableOrdered | SendOptions::ReliableUnordered
+        ));
+        let window = usize::from(window_size);
+        Self {
+            options,
+            window_size,
+            max_queue_size,
+            queue: VecDeque::new(),
+            pending: (0..window).map(|_| None).collect(),
+            local_sequence: 0,
+            local_window_start: 0,
+            remote_sequence: 0,
+            remote_window_start: 0,
+            acks: vec![0; window / usize::from(BITS_IN_BYTE)],
+            must_send_acks: false,
+            held: (0..window).map(|_| None).collect(),
+            early_received: vec![false; window],
+            packets_sent: 0,
+            resends: 0,
+        }
+    }
+    fn ordered(&self) -> bool {
+        self.options == SendOptions::ReliableOrdered
+    }
+    fn index(&self, seq: u16) -> usize {
+        usize::from(seq % self.window_size)
+    }
+    /// Queues the packets making up one message. A fragmented message is
+    /// queued whole or not at all.
+    pub(crate) fn send(
+        &mut self,
+        packets: impl IntoIterator<Item = ChannelPacket>,
+    ) -> Result<(), SendError> {
+        if self.queue.len() >= self.max_queue_size {
+            return Err(SendError::QueueFull);
+        }
+        self.queue.extend(packets);
+        Ok(())
+    }
+    /// Moves queued packets into the send window and (re)sends everything in
+    /// the window that hasn't been acknowledged within `resend_delay`.
+    pub(crate) fn send_next_packets(
+        &mut self,
+        now: Instant,
+        resend_delay: Duration,
+        out: &mut OutgoingQueue,
+    ) {
+        while relative_sequence(self.local_sequence, self.local_window_start)
+            < self.window_size as i16
+        {
+            let Some(packet) = self.queue.pop_front() else {
+                break;
+            };
+            let idx = self.index(self.local_sequence);
+            self.pending[idx] = Some(PendingPacket {
+                packet,
+                last_sent: None,
+            });
+            self.local_sequence = next_sequence(self.local_sequence);
+        }
+        let mut seq = self.local_window_start;
+        while seq != self.local_sequence {
+            let idx = self.index(seq);
+            if let Some(pending) = self.pending[idx].as_mut() {
+                let due = pending
+                    .last_sent
+                    .is_none_or(|sent| now.duration_since(sent) >= resend_delay);
+                if due {
+                    if pending.last_sent.is_some() {
+                        self.resends += 1;
+                    }
+                    self.packets_sent += 1;
+                    pending.last_sent = Some(now);
+                    let header = pending.packet.header(self.options, seq);
+                    out.push(header, &pending.packet.payload);
+                }
+            }
+            seq = next_sequence(seq);
+        }
+        if self.must_send_acks {
+            self.must_send_acks = false;
+            let header = PacketHeader {
+                sequence: self.remote_window_start,
+                channel_id: channel_id(0, self.options),
+                ..PacketHeader::new(PacketProperty::Ack)
+            };
+            out.push(header, &self.acks);
+        }
+    }
+    /// Handles a channeled or ack packet for this channel, appending any
+    /// payloads that are ready for delivery to `delivered`.
+    pub(crate) fn process_packet(
+        &mut self,
+        packet: Packet<'_>,
+        delivered: &mut Vec<ChannelPacket>,
+    ) {
+        if packet.header.property == PacketProperty::Ack {
+            self.process_ack(packet);
+            return;
+        }
+        let seq = packet.header.sequence;
+        if seq >= MAX_SEQUENCE {
+            return;
+        }
+        let window = self.window_size as i16;
+        let relate = relative_sequence(seq, self.remote_window_start);
+        if relative_sequence(seq, self.remote_sequence) > window
+            || relate < 0
+            || relate >= window * 2
+        {
+            return;
+        }
+        if relate >= window {
+            let new_window_start = ((u32::from(self.remote_window_start) + relate as u32
+                - u32::from(self.window_size)
+                + 1)
+                % u32::from(MAX_SEQUENCE)) as u16;
+            while self.remote_window_start != new_window_start {
+                self.set_ack(self.remote_window_start, false);
+                self.remote_window_start = next_sequence(self.remote_window_start);
+            }
+        }
+        // Even duplicates get acked again, since our previous ack may have
+        // been the packet that got lost.
+        self.must_send_acks = true;
+        if self.is_acked(seq) {
+            return;
+        }
+        self.set_ack(seq, true);
+
+        if seq != self.remote_sequence {
+            let idx = self.index(seq);
+            if self.ordered() {
+                self.held[idx] = Some(packet.into());
+            } else {
+                self.early_received[idx] = true;
+                delivered.push(packet.into());
+            }
+            return;
+        }
+        delivered.push(packet.into());
+        self.remote_sequence = next_sequence(self.remote_sequence);
+        loop {
+            let idx = self.index(self.remote_sequence);
+            if self.ordered() {
+                let Some(held) = self.held[idx].take() else {
+                    break;
+                };
+                delivered.push(held);
+            } else if !std::mem::take(&mut self.early_received[idx]) {
+                break;
+            }
+            self.remote_sequence = next_sequence(self.remote_sequence);
+        }
+    }
+    fn process_ack(&mut self, packet: Packet<'_>) {
+        if packet.payload.len() != self.acks.len() {
+            return;
+        }
+        let ack_window_start = packet.header.sequence;
+        let window_rel = relative_sequence(self.local_window_start, ack_window_start);
+        if ack_window_start >= MAX_SEQUENCE
+            || window_rel < 0
+            || window_rel >= self.window_size as i16
+        {
+            return;
+        }
+        let mut seq = self.local_window_start;
+        while seq != self.local_sequence {
+            if relative_sequence(seq, ack_window_start) >= self.window_size as i16 {
+                break;
+            }
+            let idx = self.index(seq);
+            let (byte, bit) = (
+                idx / usize::from(BITS_IN_BYTE),
+                idx % usize::from(BITS_IN_BYTE),
+            );
+            if packet.payload[byte] & (1 << bit) != 0 {
+                self.pending[idx] = None;
+            }
+            seq = next_sequence(seq);
+        }
+        while self.local_window_start != self.local_sequence
+            && self.pending[self.index(self.local_window_start)].is_none()
+        {
+            self.local_window_start = next_sequence(self.local_window_start);
+        }
+    }
+    /// Data packets sent, resends included.
+    pub(crate) fn packets_sent(&self) -> u64 {
+        self.packets_sent
+    }
+    pub(crate) fn resends(&self) -> u64 {
+        self.resends
+    }
+    fn is_acked(&self, seq: u16) -> bool {
+        let idx = self.index(seq);
+        self.acks[idx / usize::from(BITS_IN_BYTE)] & (1 << (idx % usize::from(BITS_IN_BYTE))) != 0
+    }
+    fn set_ack(&mut self, seq: u16, value: bool) {
+        let idx = self.index(seq);
+        let (byte, bit) = (
+            idx / usize::from(BITS_IN_BYTE),
+            idx % usize::from(BITS_IN_BYTE),
+        );
+        if value {
+            self.acks[byte] |= 1 << bit;
+        } else {
+            self.acks[byte] &= !(1 << bit);
+        }
+    }
+}
+
+/// Unreliable delivery that only ever moves forward: a packet older than the
+/// newest one delivered is dropped instead of buffered.
+#[derive(Debug, Default)]
+pub(crate) struct SequencedChannel {
+    local_sequence: u16,
+    remote_sequence: Option<u16>,
+    dropped_stale: u64,
+}
+
+impl SequencedChannel {
+    pub(crate) fn send(&mut self, payload: &[u8], out: &mut OutgoingQueue) {
+        out.push(
+            PacketHeader::channeled(SendOptions::Sequenced, self.local_sequence),
+            payload,
+        );
+        self.local_sequence = next_sequence(self.local_sequence);
+    }
+    pub(crate) fn process_packet(
+        &mut self,
+        packet: Packet<'_>,
+        delivered: &mut Vec<ChannelPacket>,
+    ) {
+        let seq = packet.header.sequence;
+        if seq >= MAX_SEQUENCE {
+            return;
+        }
+        let newer = self
+            .remote_sequence
+            .is_none_or(|last| relative_sequence(seq, last) > 0);
+        if !newer {
+            self.dropped_stale += 1;
+            return;
+        }
+        self.remote_sequence = Some(seq);
+        delivered.push(packet.into());
+    }
+    pub(crate) fn dropped_stale(&self) -> u64 {
+        self.dropped_stale
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use super::*;
+    use crate::{
+        event_code::NetworkEvent,
+        net::{
+            MemoryTransport, NetClient, NetEventListener, NetPeer, NetServer, NetworkConditions,
+            SimulatedTransport,
+            peer::tests::{config, deliver, pair},
+        },
+    };
+
+    const LOSSY: NetworkConditions = NetworkConditions {
+        loss: 0.2,
+        latency: Duration::from_millis(30),
+        jitter: Duration::from_millis(20),
+        duplicate: 0.05,
+        reorder: 0.1,
+        reorder_delay: Duration::from_millis(50),
+    };
+
+    #[derive(Default)]
+    struct Received(Vec<Vec<u8>>);
+
+    impl NetEventListener for Received {
+        fn on_receive(&mut self, _peer: &mut NetPeer, payload: &[u8], _options: SendOptions) {
+            self.0.push(payload.to_vec());
+        }
+    }
+
+    fn message(index: u16) -> Vec<u8> {
+        let [low, high] = index.to_le_bytes();
+        vec![NetworkEvent::DamageCube as u8, low, high]
+    }
+
+    fn index(message: &[u8]) -> u16 {
+        u16::from_le_bytes([message[1], message[2]])
+    }
+
+    /// Sends `count` messages from a client to a server over a lossy link,
+    /// returning what the server received.
+    fn send_lossy(options: SendOptions, count: u16) -> Vec<Vec<u8>> {
+        let server_addr = SocketAddr::from(([10, 0, 0, 1], 1));
+        let (server_transport, client_transport) =
+            MemoryTransport::pair(server_addr, SocketAddr::from(([10, 0, 0, 2], 2)));
+        let mut server = NetServer::new(
+            SimulatedTransport::new(server_transport, LOSSY, 1),
+            config(),
+        );
+        let mut client = NetClient::new(
+            SimulatedTransport::new(client_transport, LOSSY, 2),
+            server_addr,
+            config(),
+            b"",
+        );
+        let (mut on_server, mut on_client) = (Received::default(), Received::default());
+        let mut now = Instant::now();
+        let mut sent = 0;
+        for _ in 0..1000 {
+            now += Duration::from_millis(10);
+            client.poll_at(now, &mut on_client).unwrap();
+            server.poll_at(now, &mut on_server).unwrap();
+            while client.is_connected()
+                && sent < count
+                && client.send(&message(sent), options).is_ok()
+            {
+                sent += 1;
+            }
+        }
+        assert_eq!(sent, count);
+        on_server.0
+    }
+
+    #[test]
+    fn ordered_delivery_survives_loss_and_reordering() {
+        let received = send_lossy(SendOptions::ReliableOrdered, 300);
+        assert_eq!(received, (0..300).map(message).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn unordered_delivery_survives_loss_and_reordering() {
+        let mut received = send_lossy(SendOptions::ReliableUnordered, 300);
+        received.sort_by_key(|message| index(message));
+        assert_eq!(received, (0..300).map(message).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn lost_packets_are_resent() {
+        for options in [SendOptions::ReliableOrdered, SendOptions::ReliableUnordered] {
+            let mut now = Instant::now();
+            let (mut server, mut client) = pair(now);
+            for i in 0..100 {
+                client.send(&[i], options).unwrap();
+            }
+            let (mut sent, mut received) = (0, Vec::new());
+            for _ in 0..100 {
+                // Long enough for everything unacked to be resent.
+                now += Duration::from_millis(50);
+                client.update(now);
+                for datagram in client.drain_outgoing().collect::<Vec<_>>() {
+                    sent += 1;
+                    // Every third datagram is lost.
+                    if sent % 3 != 0 {
+                        server.process_packet(Packet::parse(&datagram).unwrap(), now);
+                    }
+                }
+                received.extend(
+                    server
+                        .take_received()
+                        .into_iter()
+                        .map(|(payload, _)| payload[0]),
+                );
+                deliver(&mut server, &mut client, now);
+            }
+            if options == SendOptions::ReliableUnordered {
+                received.sort_unstable();
+            }
+            assert!(received.into_iter().eq(0..100));
+        }
+    }
+
+    #[test]
+    fn sequence_wraps_around() {
+        let count = u32::from(MAX_SEQUENCE) + 1000;
+        for options in [SendOptions::ReliableOrdered, SendOptions::ReliableUnordered] {
+            let now = Instant::now();
+            let (mut server, mut client) = pair(now);
+            let (mut sent, mut received) = (0, Vec::new());
+            while received.len() < count as usize {
+                while sent < count && client.send(&sent.to_le_bytes(), options).is_ok() {
+                    sent += 1;
+                }
+                let delivered = deliver(&mut client, &mut server, now);
+                assert!(!delivered.is_empty(), "stalled after {}", received.len());
+                received.extend(
+                    delivered
+                        .into_iter()
+                        .map(|(payload, _)| u32::from_le_bytes(payload.try_into().unwrap())),
+                );
+                deliver(&mut server, &mut client, now);
+            }
+            if options == SendOptions::ReliableUnordered {
+                received.sort_unstable();
+            }
+            assert!(received.into_iter().eq(0..count));
+        }
+    }
+
+    fn receive_sequenced(channel: &mut SequencedChannel, sequences: &[u16]) -> Vec<u16> {
+        let mut delivered = Vec::new();
+        for &seq in sequences {
+            let bytes = seq.to_le_bytes();
+            let header = PacketHeader::channeled(SendOptions::Sequenced, seq);
+            channel.process_packet(Packet::new(header, &bytes), &mut delivered);
+        }
+        delivered
+            .into_iter()
+            .map(|packet| u16::from_le_bytes(packet.payload.try_into().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn sequenced_drops_stale_packets() {
+        let mut channel = SequencedChannel::default();
+        assert_eq!(
+            receive_sequenced(&mut channel, &[10, 8, 10, 12, 11]),
+            [10, 12]
+        );
+        assert_eq!(channel.dropped_stale(), 3);
+
+        let mut channel = SequencedChannel::default();
+        let last = MAX_SEQUENCE - 1;
+        assert_eq!(
+            receive_sequenced(&mut channel, &[last - 1, last, 0, last, 1]),
+            [last - 1, last, 0, 1]
+        );
+        assert_eq!(channel.dropped_stale(), 1);
+    }
+
+    #[test]
+    fn sequenced_delivery_only_moves_forward() {
+        let received = send_lossy(SendOptions::Sequenced, 300);
+        assert!(!received.is_empty());
+        assert!(
+            received
+                .windows(2)
+                .all(|pair| index(&pair[0]) < index(&pair[1]))
+        );
+    }
+}
@@ -0,0 +1,264 @@
+use std::{
+    io,
+    net::{SocketAddr, ToSocketAddrs},
+    time::{Duration, Instant},
+};
+
+use byteserde::prelude::ByteSerializeHeap;
+
+use crate::{
+    event_code::NetworkEvent,
+    events::Role,
+    net::{
+        EventError, NetEventListener, SendError, SendOptions, decode_event,
+        packet::{
+            ConnectAcceptPacket, ConnectRequestPacket, DisconnectPacket, PROTOCOL_ID, Packet,
+            PacketHeader, PacketProperty,
+        },
+        peer::{ConnectionState, DisconnectReason, NetPeer, PeerId, PeerSettings, dotnet_ticks},
+        server::{MAX_DATAGRAM_SIZE, send_datagram},
+        session::ReconnectToken,
+        transport::{Transport, UdpTransport},
+    },
+    photon::enter_battle::NetworkConfig,
+};
+
+// LiteNetLib's defaults for ReconnectDelay and MaxConnectAttempts.
+const CONNECT_RETRY_DELAY: Duration = Duration::from_millis(500);
+const MAX_CONNECT_ATTEMPTS: u32 = 10;
+
+/// A single connection to a game server, for bots, load testers and other
+/// tooling.
+///
+/// The connect request is retried until the server answers; if it never
+/// does, the connection ends with [`DisconnectReason::Timeout`].
+pub struct NetClient<T = UdpTransport> {
+    transport: T,
+    config: NetworkConfig,
+    peer: NetPeer,
+    connect_attempts: u32,
+    last_connect_attempt: Option<Instant>,
+    disconnect_raised: bool,
+    reconnect_token: Option<ReconnectToken>,
+    recv_buf: Vec<u8>,
+}
+
+impl NetClient {
+    /// Connects over UDP from an ephemeral local port. `data` is attached to
+    /// the connect request.
+    pub fn connect(
+        server: impl ToSocketAddrs,
+        config: NetworkConfig,
+        data: &[u8],
+    ) -> io::Result<Self> {
+        let server = server
+            .to_socket_addrs()?
+            .next()
+            .ok_or(io::ErrorKind::InvalidInput)?;
+        let local = if server.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        };
+        Ok(Self::new(UdpTransport::bind(local)?, server, config, data))
+    }
+}
+
+impl<T: Transport> NetClient<T> {
+    pub fn new(transport: T, server: SocketAddr, config: NetworkConfig, data: &[u8]) -> Self {
+        let peer = NetPeer::new(
+            PeerId(0),
+            server,
+            dotnet_ticks(),
+            0,
+            data.to_vec(),
+            PeerSettings::new(&config, Role::Client),
+            Instant::now(),
+        );
+        Self {
+            transport,
+            config,
+            peer,
+            connect_attempts: 0,
+            last_connect_attempt: None,
+            disconnect_raised: false,
+            reconnect_token: None,
+            recv_buf: vec![0; MAX_DATAGRAM_SIZE],
+        }
+    }
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.transport.local_addr()
+    }
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+    pub fn config(&self) -> &NetworkConfig {
+        &self.config
+    }
+    /// The connection to the server.
+    pub fn peer(&self) -> &NetPeer {
+        &self.peer
+    }
+    pub fn peer_mut(&mut self) -> &mut NetPeer {
+        &mut self.peer
+    }
+    pub fn state(&self) -> ConnectionState {
+        self.peer.state()
+    }
+    pub fn is_connected(&self) -> bool {
+        self.peer.is_connected()
+    }
+    /// The token a server tracking sessions handed out, if this client
+    /// connected with [`ReconnectToken::request`] as its data. Reconnect
+    /// with [`ReconnectToken::connect_data`] to rejoin as the same player.
+    pub fn reconnect_token(&self) -> Option<ReconnectToken> {
+        self.reconnect_token
+    }
+    pub fn send(&mut self, payload: &[u8], options: SendOptions) -> Result<(), SendError> {
+        self.peer.send(payload, options)
+    }
+    pub fn send_event(
+        &mut self,
+        code: NetworkEvent,
+        data: &impl ByteSerializeHeap,
+        options: SendOptions,
+    ) -> Result<(), EventError> {
+        self.peer.send_event(code, data, options)
+    }
+    /// Closes the connection. The disconnect callback fires on the next
+    /// [`NetClient::poll`] with [`DisconnectReason::Shutdown`].
+    pub fn disconnect(&mut self) {
+        self.peer.close(self.peer.clock());
+    }
+    /// Receives all pending datagrams, dispatches them to `listener` and
+    /// flushes outgoing packets. Never blocks.
+    pub fn poll(&mut self, listener: &mut impl NetEventListener) -> io::Result<()> {
+        self.poll_at(Instant::now(), listener)
+    }
+    /// [`NetClient::poll`] with an explicit clock.
+    pub fn poll_at(
+        &mut self,
+        now: Instant,
+        listener: &mut impl NetEventListener,
+    ) -> io::Result<()> {
+        self.transport.advance(now);
+        if self.peer.state() == ConnectionState::Connecting {
+            self.retry_connect(now);
+        }
+        let mut buf = std::mem::take(&mut self.recv_buf);
+        let result = loop {
+            match self.transport.recv_from(&mut buf) {
+                Ok(Some((len, addr))) => {
+                    if addr == self.peer.addr() {
+                        self.handle_datagram(&buf[..len], now, listener);
+                    }
+                }
+                Ok(None) => break Ok(()),
+                Err(e) => break Err(e),
+            }
+        };
+        self.recv_buf = buf;
+        result?;
+        self.peer.update(now);
+        let addr = self.peer.addr();
+        for datagram in self.peer.drain_outgoing() {
+            send_datagram(&mut self.transport, addr, &datagram);
+        }
+        if self.peer.state() == ConnectionState::Disconnected && !self.disconnect_raised {
+            self.disconnect_raised = true;
+            let reason = self
+                .peer
+                .disconnect_reason()
+                .unwrap_or(DisconnectReason::Shutdown);
+            listener.on_peer_disconnected(&self.peer, reason);
+        }
+        Ok(())
+    }
+
+    fn retry_connect(&mut self, now: Instant) {
+        let due = self
+            .last_connect_attempt
+            .is_none_or(|last| now.duration_since(last) >= CONNECT_RETRY_DELAY);
+        if !due {
+            return;
+        }
+        if self.connect_attempts == MAX_CONNECT_ATTEMPTS {
+            self.peer.set_disconnected(DisconnectReason::Timeout);
+            return;
+        }
+        self.connect_attempts += 1;
+        self.last_connect_attempt = Some(now);
+        let mut body = Vec::new();
+        ConnectRequestPacket {
+            protocol_id: PROTOCOL_ID,
+            connection_time: self.peer.connection_time(),
+            data: self.peer.connect_data(),
+        }
+        .write(&mut body);
+        self.peer
+            .send_raw(PacketHeader::new(PacketProperty::ConnectRequest), &body);
+    }
+    fn handle_datagram(&mut self, data: &[u8], now: Instant, listener: &mut impl NetEventListener) {
+        let Ok(packet) = Packet::parse(data) else {
+            return;
+        };
+        let peer = &mut self.peer;
+        if packet.header.connection_number != peer.connection_number() {
+            return;
+        }
+        match (packet.header.property, peer.state()) {
+            (PacketProperty::ConnectAccept, ConnectionState::Connecting) => {
+                let Ok(accept) = ConnectAcceptPacket::parse(packet.payload) else {
+                    return;
+                };
+                if accept.connection_time != peer.connection_time() {
+                    return;
+                }
+                peer.set_connected(now);
+                listener.on_peer_connected(peer);
+            }
+            (PacketProperty::InvalidProtocol, ConnectionState::Connecting) => {
+                peer.set_disconnected(DisconnectReason::Rejected);
+            }
+            (PacketProperty::Disconnect, state) => {
+                let Ok(disconnect) = DisconnectPacket::parse(packet.payload) else {
+                    return;
+                };
+                if disconnect.connection_time != peer.connection_time() {
+                    return;
+                }
+                match state {
+                    ConnectionState::Connecting => {
+                        peer.set_disconnected(DisconnectReason::Rejected);
+                    }
+                    ConnectionState::Connected => {
+                        peer.send_raw(PacketHeader::new(PacketProperty::ShutdownOk), &[]);
+                        peer.set_disconnected(DisconnectReason::RemoteClose);
+                    }
+                    ConnectionState::Disconnected => {}
+                }
+            }
+            (_, ConnectionState::Connected) => {
+                peer.process_packet(packet, now);
+                for (payload, options) in peer.take_received() {
+                    if ReconnectToken::requested(peer.connect_data())
+                        && let Some(token) = ReconnectToken::from_message(&payload)
+                    {
+                        self.reconnect_token = Some(token);
+                        continue;
+                    }
+                    if let Ok((code, _)) = decode_event(&payload)
+                        && !code.accepted_by(Role::Client)
+                    {
+                        continue;
+                    }
+                    listener.on_receive(peer, &payload, options);
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use std::{io, net::SocketAddr};
+
+use crate::{
+    net::{
+        packet::{Packet, PacketHeader, PacketProperty},
+        transport::Transport,
+    },
+    photon::enter_battle::{BattleParametersData, GameModeKey, GameModeType},
+};
+
+/// Payload of a discovery request, so unrelated LiteNetLib broadcasts on the
+/// same port are ignored.
+pub const DISCOVERY_REQUEST: &[u8] = b"rlnl-discover";
+/// Starts a discovery response, so other unconnected messages aren't taken
+/// for one.
+pub const DISCOVERY_RESPONSE: &[u8] = b"rlnl-server";
+
+// port + player count + game mode + ranked + custom + map name length
+const RESPONSE_FIXED_SIZE: usize = 4 + 2 + 1 + 1 + 1 + 2;
+
+/// What a battle server advertises on the LAN.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerInfo {
+    pub map_name: String,
+    pub game_mode: GameModeKey,
+    pub port: i32,
+}
+
+impl From<&BattleParametersData> for ServerInfo {
+    fn from(params: &BattleParametersData) -> Self {
+        Self {
+            map_name: params.map_name.clone(),
+            game_mode: params.game_mode,
+            port: params.host_port,
+        }
+    }
+}
+
+/// A server's answer to a discovery request, sent as an
+/// [`PacketProperty::UnconnectedMessage`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveryResponse {
+    pub info: ServerInfo,
+    pub player_count: u16,
+}
+
+impl DiscoveryResponse {
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        let payload = payload.strip_prefix(DISCOVERY_RESPONSE)?;
+        let (fixed, rest) = payload.split_first_chunk::<RESPONSE_FIXED_SIZE>()?;
+        let name_len = usize::from(u16::from_le_bytes([fixed[9], fixed[10]]));
+        let map_name = std::str::from_utf8(rest.get(..name_len)?).ok()?;
+        Some(Self {
+            info: ServerInfo {
+                map_name: map_name.to_owned(),
+                game_mode: GameModeKey {
+                    ty: GameModeType::from_repr(fixed[6])?,
+                    is_ranked: fixed[7] != 0,
+                    is_custom: fixed[8] != 0,
+                },
+                port: i32::from_le_bytes(fixed[0..4].try_into().unwrap()),
+            },
+            player_count: u16::from_le_bytes([fixed[4], fixed[5]]),
+        })
+    }
+    pub fn write(&self, out: &mut Vec<u8>) {
+        let name = self.info.map_name.as_bytes();
+        let name = &name[..name.len().min(u16::MAX.into())];
+        out.extend_from_slice(DISCOVERY_RESPONSE);
+        out.extend_from_slice(&self.info.port.to_le_bytes());
+        out.extend_from_slice(&self.player_count.to_le_bytes());
+        out.push(self.info.game_mode.ty as u8);
+        out.push(self.info.game_mode.is_ranked.into());
+        out.push(self.info.game_mode.is_custom.into());
+        out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        out.extend_from_slice(name);
+    }
+}
+
+/// Sends a discovery request to `addr`, usually a broadcast address on the
+/// port servers listen on. Answers arrive as datagrams that
+/// [`parse_discovery_response`] recognizes.
+pub fn send_discovery_request(transport: &mut impl Transport, addr: SocketAddr) -> io::Result<()> {
+    let request = Packet::new(
+        PacketHeader::new(PacketProperty::Broadcast),
+        DISCOVERY_REQUEST,
+    );
+    transport.send_to(&request.to_vec(), addr)
+}
+
+pub fn parse_discovery_response(datagram: &[u8]) -> Option<DiscoveryResponse> {
+    let packet = Packet::parse(datagram).ok()?;
+    if packet.header.property != PacketProperty::UnconnectedMessage {
+        return None;
+    }
+    DiscoveryResponse::parse(packet.payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::{MemoryTransport, NetEventListener, NetServer, peer::tests::config};
+
+    fn response() -> DiscoveryResponse {
+        DiscoveryResponse {
+            info: ServerInfo {
+                map_name: "Sandbox".to_owned(),
+                game_mode: GameModeKey {
+                    ty: GameModeType::TeamDeathmatch,
+                    is_ranked: false,
+                    is_custom: true,
+                },
+                port: 7777,
+            },
+            player_count: 3,
+        }
+    }
+
+    #[test]
+    fn response_round_trip() {
+        let mut body = Vec::new();
+        response().write(&mut body);
+        assert_eq!(
+            body.len(),
+            DISCOVERY_RESPONSE.len() + RESPONSE_FIXED_SIZE + "Sandbox".len()
+        );
+        assert_eq!(DiscoveryResponse::parse(&body), Some(response()));
+    }
+
+    #[test]
+    fn rejects_short_and_unrelated_payloads() {
+        let mut body = Vec::new();
+        response().write(&mut body);
+        for len in 0..body.len() {
+            assert_eq!(DiscoveryResponse::parse(&body[..len]), None);
+        }
+        body[0] ^= 0xFF;
+        assert_eq!(DiscoveryResponse::parse(&body), None);
+        // The right payload in the wrong kind of packet.
+        body[0] ^= 0xFF;
+        let header = PacketHeader::new(PacketProperty::Unreliable);
+        assert_eq!(
+            parse_discovery_response(&Packet::new(header, &body).to_vec()),
+            None
+        );
+    }
+
+    struct Quiet;
+
+    impl NetEventListener for Quiet {}
+
+    #[test]
+    fn server_answers_discovery_requests() {
+        let server_addr = SocketAddr::from(([10, 0, 0, 1], 1));
+        let (server, mut client) =
+            MemoryTransport::pair(server_addr, SocketAddr::from(([10, 0, 0, 2], 2)));
+        let mut server = NetServer::new(server, config());
+        let mut buf = [0; 256];
+
+        // Silent until it has something to advertise.
+        send_discovery_request(&mut client, server_addr).unwrap();
+        server.poll(&mut Quiet).unwrap();
+        assert_eq!(client.recv_from(&mut buf).unwrap(), None);
+
+        server.set_discovery(Some(response().info));
+        let other = Packet::new(PacketHeader::new(PacketProperty::Broadcast), b"hello");
+        client.send_to(&other.to_vec(), server_addr).unwrap();
+        send_discovery_request(&mut client, server_addr).unwrap();
+        server.poll(&mut Quiet).unwrap();
+        let (len, from) = client.recv_from(&mut buf).unwrap().unwrap();
+        assert_eq!(from, server_addr);
+        assert_eq!(
+            parse_discovery_response(&buf[..len]),
+            Some(DiscoveryResponse {
+                player_count: 0,
+                ..response()
+            })
+        );
+        // Only the real request was answered.
+        assert_eq!(client.recv_from(&mut buf).unwrap(), None);
+    }
+}
@@ -0,0 +1,250 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use crate::net::{channel::ChannelPacket, packet::FragmentHeader};
+
+// Channels that fragment: reliable unordered and reliable ordered.
+const FRAGMENTING_CHANNELS: usize = 2;
+
+/// Splits `payload` into packets of at most `max_fragment_size` bytes.
+/// Payloads of up to `max_payload_size` bytes are returned as a single
+/// unfragmented packet.
+pub(crate) fn split(
+    payload: &[u8],
+    max_payload_size: usize,
+    max_fragment_size: usize,
+    fragment_id: u16,
+) -> Vec<ChannelPacket> {
+    if payload.len() <= max_payload_size {
+        return vec![ChannelPacket {
+            fragment: None,
+            payload: payload.to_vec(),
+        }];
+    }
+    let total = payload.len().div_ceil(max_fragment_size);
+    payload
+        .chunks(max_fragment_size)
+        .enumerate()
+        .map(|(part, chunk)| ChannelPacket {
+            fragment: Some(FragmentHeader {
+                fragment_id,
+                fragment_part: part as u16,
+                fragments_total: total as u16,
+            }),
+            payload: chunk.to_vec(),
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+struct IncomingFragments {
+    parts: Vec<Option<Vec<u8>>>,
+    received: usize,
+    size: usize,
+    started: Instant,
+}
+
+/// Reassembles fragmented messages, keyed by fragment id like LiteNetLib.
+///
+/// Bytes held for incomplete messages are capped; a fragment that would go
+/// over the cap, or belongs to a message larger than `max_message_size`, is
+/// dropped and its message eventually expires after `timeout`.
+///
+/// Fragments reach the assembler after their channel has acked them, so a
+/// dropped fragment is never resent. The cap is therefore sized so a sender
+/// that stays within its window can't reach it: every incomplete message is
+/// missing a packet at or past the receive window's start, so everything held
+/// lies between one message before the window and the window's end.
+#[derive(Debug)]
+pub(crate) struct FragmentAssembler {
+    incomplete: HashMap<u16, IncomingFragments>,
+    held_bytes: usize,
+    max_held_bytes: usize,
+    max_message_size: usize,
+    max_fragment_size: usize,
+    timeout: Duration,
+}
+
+impl FragmentAssembler {
+    pub(crate) fn new(
+        max_message_size: usize,
+        max_fragment_size: usize,
+        window_size: u16,
+        timeout: Duration,
+    ) -> Self {
+        let per_channel = usize::from(window_size) * max_fragment_size + max_message_size;
+        Self {
+            incomplete: HashMap::new(),
+            held_bytes: 0,
+            max_held_bytes: per_channel * FRAGMENTING_CHANNELS,
+            max_message_size,
+            max_fragment_size,
+            timeout,
+        }
+    }
+    /// Stores one fragment, returning the whole message once every part has
+    /// arrived.
+    pub(crate) fn insert(
+        &mut self,
+        header: FragmentHeader,
+        payload: Vec<u8>,
+        now: Instant,
+    ) -> Option<Vec<u8>> {
+        let total = usize::from(header.fragments_total);
+        let part = usize::from(header.fragment_part);
+        if part >= total
+            || payload.len() > self.max_fragment_size
+            || total.saturating_mul(self.max_fragment_size)
+                > self.max_message_size + self.max_fragment_size
+            || self.held_bytes + payload.len() > self.max_held_bytes
+        {
+            return None;
+        }
+        let entry = self
+            .incomplete
+            .entry(header.fragment_id)
+            .or_insert_with(|| IncomingFragments {
+                parts: vec![None; total],
+                received: 0,
+                size: 0,
+                started: now,
+            });
+        if entry.parts.len() != total || entry.parts[part].is_some() {
+            return None;
+        }
+        self.held_bytes += payload.len();
+        entry.size += payload.len();
+        entry.received += 1;
+        entry.parts[part] = Some(payload);
+        if entry.received < total {
+            return None;
+        }
+        let entry = self.incomplete.remove(&header.fragment_id)?;
+        self.held_bytes -= entry.size;
+        let mut message = Vec::with_capacity(entry.size);
+        for part in entry.parts.into_iter().flatten() {
+            message.extend_from_slice(&part);
+        }
+        Some(message)
+    }
+    /// Drops messages that have been incomplete for longer than the timeout.
+    pub(crate) fn expire(&mut self, now: Instant) {
+        let timeout = self.timeout;
+        let mut freed = 0;
+        self.incomplete.retain(|_, fragments| {
+            let keep = now.duration_since(fragments.started) < timeout;
+            if !keep {
+                freed += fragments.size;
+            }
+            keep
+        });
+        self.held_bytes -= freed;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MESSAGE: usize = 3000;
+    const FRAGMENT: usize = 1300;
+    const WINDOW: u16 = 64;
+
+    fn assembler() -> FragmentAssembler {
+        FragmentAssembler::new(MESSAGE, FRAGMENT, WINDOW, Duration::from_secs(5))
+    }
+
+    fn parts(message: &[u8], id: u16) -> Vec<(FragmentHeader, Vec<u8>)> {
+        split(message, 0, FRAGMENT, id)
+            .into_iter()
+            .map(|packet| (packet.fragment.unwrap(), packet.payload))
+            .collect()
+    }
+
+    #[test]
+    fn holds_a_full_window_of_first_fragments() {
+        let mut assembler = assembler();
+        let now = Instant::now();
+        let message = vec![7; MESSAGE];
+        let messages: Vec<_> = (0..WINDOW).map(|id| parts(&message, id)).collect();
+        for part in 0..messages[0].len() - 1 {
+            for fragments in &messages {
+                let (header, payload) = fragments[part].clone();
+                assert_eq!(assembler.insert(header, payload, now), None);
+            }
+        }
+        for fragments in &messages {
+            let (header, payload) = fragments.last().unwrap().clone();
+            assert_eq!(
+                assembler.insert(header, payload, now),
+                Some(message.clone())
+            );
+        }
+        assert_eq!(assembler.held_bytes, 0);
+    }
+
+    #[test]
+    fn small_payloads_are_not_fragmented() {
+        let packets = split(&[1, 2, 3], 3, FRAGMENT, 0);
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].fragment, None);
+    }
+
+    #[test]
+    fn reassembles_fragments_in_any_order() {
+        let mut assembler = assembler();
+        let now = Instant::now();
+        let message: Vec<u8> = (0..MESSAGE).map(|i| i as u8).collect();
+        let mut fragments = parts(&message, 9);
+        assert_eq!(fragments.len(), 3);
+        fragments.swap(0, 2);
+        let (last, rest) = fragments.split_last().unwrap();
+        for (header, payload) in rest.iter().cloned() {
+            assert_eq!(assembler.insert(header, payload, now), None);
+        }
+        // A repeat of a part already held changes nothing.
+        let (header, payload) = rest[0].clone();
+        assert_eq!(assembler.insert(header, payload, now), None);
+        let (header, payload) = last.clone();
+        assert_eq!(assembler.insert(header, payload, now), Some(message));
+        assert_eq!(assembler.held_bytes, 0);
+    }
+
+    #[test]
+    fn rejects_fragments_of_oversized_messages() {
+        let mut assembler = assembler();
+        let header = FragmentHeader {
+            fragment_id: 0,
+            fragment_part: 0,
+            fragments_total: 4,
+        };
+        assert_eq!(
+            assembler.insert(header, vec![0; FRAGMENT], Instant::now()),
+            None
+        );
+        assert_eq!(assembler.held_bytes, 0);
+    }
+
+    #[test]
+    fn expires_incomplete_messages() {
+        let mut assembler = assembler();
+        let start = Instant::now();
+        let message = vec![7; MESSAGE];
+        let fragments = parts(&message, 1);
+        for (header, payload) in fragments[..2].iter().cloned() {
+            assert_eq!(assembler.insert(header, payload, start), None);
+        }
+        assembler.expire(start + Duration::from_secs(4));
+        assert_eq!(assembler.held_bytes, 2 * FRAGMENT);
+        assembler.expire(start + Duration::from_secs(6));
+        assert_eq!(assembler.held_bytes, 0);
+        // The rest of an expired message starts over and never completes.
+        let (header, payload) = fragments[2].clone();
+        assert_eq!(
+            assembler.insert(header, payload, start + Duration::from_secs(6)),
+            None
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+use byteserde::des_slice::from_slice;
+
+use crate::{
+    event_code::NetworkEvent,
+    events::{CommandOnly, loading::GameGuid},
+    net::{NetPeer, PeerId, SendOptions},
+    photon::enter_battle::BattleParametersData,
+    types::{GameServerErrorCodes, StringCode},
+};
+
+/// Checks that connecting clients were sent to this battle, by comparing the
+/// GUID they send in `ValidateGameGuid` with the one from `EnterBattle`.
+///
+/// Installed with [`NetServer::set_game_guid`](crate::net::NetServer::set_game_guid).
+/// Until a peer's GUID checks out, the server drops everything else it sends;
+/// a peer that doesn't send one within the timeout is disconnected.
+#[derive(Debug, Clone)]
+pub struct GameGuidValidator {
+    game_guid: String,
+    timeout: Duration,
+    pending: HashMap<PeerId, Instant>,
+    validated: HashSet<PeerId>,
+}
+
+impl GameGuidValidator {
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+    pub fn new(game_guid: impl Into<String>) -> Self {
+        Self {
+            game_guid: game_guid.into(),
+            timeout: Self::DEFAULT_TIMEOUT,
+            pending: HashMap::new(),
+            validated: HashSet::new(),
+        }
+    }
+    /// How long a new peer has to send `ValidateGameGuid`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+    pub fn is_validated(&self, peer: PeerId) -> bool {
+        self.validated.contains(&peer)
+    }
+    pub(crate) fn connected(&mut self, peer: PeerId, now: Instant) {
+        self.pending.insert(peer, now);
+    }
+    /// Checks the body of a `ValidateGameGuid` event from `peer`. A match is
+    /// answered with `GameGuidValidated`; anything else is rejected.
+    pub(crate) fn validate(&mut self, peer: &mut NetPeer, body: &[u8], now: Instant) -> bool {
+        let matches = from_slice::<GameGuid>(body)
+            .is_ok_and(|sent| sent.guid.eq_ignore_ascii_case(&self.game_guid));
+        if !matches {
+            reject(peer, now);
+            return false;
+        }
+        self.pending.remove(&peer.id());
+        self.validated.insert(peer.id());
+        // If this fails the peer is already gone.
+        let _ = peer.send_event(
+            NetworkEvent::GameGuidValidated,
+            &CommandOnly,
+            SendOptions::ReliableOrdered,
+        );
+        true
+    }
+    /// Rejects `peer` if it has been waiting on validation for too long.
+    pub(crate) fn check_timeout(&mut self, peer: &mut NetPeer, now: Instant) {
+        if !peer.is_connected() || self.is_validated(peer.id()) {
+            return;
+        }
+        // Peers connected before the validator was installed start now.
+        let since = *self.pending.entry(peer.id()).or_insert(now);
+        if now.duration_since(since) > self.timeout {
+            reject(peer, now);
+        }
+    }
+    pub(crate) fn forget(&mut self, peer: PeerId) {
+        self.pending.remove(&peer);
+        self.validated.remove(&peer);
+    }
+}
+
+impl From<&BattleParametersData> for GameGuidValidator {
+    fn from(params: &BattleParametersData) -> Self {
+        Self::new(params.game_guid.clone())
+    }
+}
+
+/// Sends a `WarnPlayer` with [`GameServerErrorCodes::StrErrIncorrectGameGuid`]
+/// and disconnects.
+fn reject(peer: &mut NetPeer, now: Instant) {
+    let warning = StringCode {
+        ty: GameServerErrorCodes::StrErrIncorrectGameGuid,
+        custom: None,
+    };
+    let _ = peer.send_event(
+        NetworkEvent::WarnPlayer,
+        &warning,
+        SendOptions::ReliableOrdered,
+    );
+    peer.close(now);
+}
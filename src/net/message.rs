@@ -0,0 +1,129 @@
+use std::fmt;
+
+use byteserde::{
+    error::SerDesError,
+    prelude::{ByteSerializeHeap, ByteSerializerHeap},
+};
+
+use crate::{
+    event_code::NetworkEvent,
+    events::{GameMode, TypedEvent},
+    net::{NetPeer, SendError, SendOptions},
+};
+
+/// Errors from encoding, decoding or sending a [`NetworkEvent`] message.
+#[derive(Debug)]
+pub enum EventError {
+    Empty,
+    UnknownCode(u8),
+    /// A known code the game mode doesn't use.
+    Unregistered(NetworkEvent),
+    /// An event the receiving side isn't meant to get, such as a
+    /// server-only event sent by a client.
+    WrongDirection(NetworkEvent),
+    /// Decoding as one event found another.
+    UnexpectedCode {
+        expected: NetworkEvent,
+        found: NetworkEvent,
+    },
+    Serdes(SerDesError),
+    Send(SendError),
+}
+
+impl fmt::Display for EventError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EventError::Empty => write!(f, "empty event message"),
+            EventError::UnknownCode(code) => write!(f, "unknown event code {code}"),
+            EventError::Unregistered(code) => {
+                write!(f, "event {code:?} is not registered for this game mode")
+            }
+            EventError::WrongDirection(code) => {
+                write!(f, "event {code:?} was sent the wrong way")
+            }
+            EventError::UnexpectedCode { expected, found } => {
+                write!(f, "expected event {expected:?}, found {found:?}")
+            }
+            EventError::Serdes(e) => write!(f, "malformed event body: {}", e.message),
+            EventError::Send(e) => write!(f, "failed to send event: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for EventError {}
+
+impl From<SerDesError> for EventError {
+    fn from(value: SerDesError) -> Self {
+        EventError::Serdes(value)
+    }
+}
+
+impl From<SendError> for EventError {
+    fn from(value: SendError) -> Self {
+        EventError::Send(value)
+    }
+}
+
+/// Encodes an event message: the event code byte followed by `data`.
+pub fn encode_event(
+    code: NetworkEvent,
+    data: &impl ByteSerializeHeap,
+) -> Result<Vec<u8>, EventError> {
+    let mut ser = ByteSerializerHeap::default();
+    ser.serialize_bytes_slice(&[code as u8])?;
+    ser.serialize(data)?;
+    Ok(ser.as_slice().to_vec())
+}
+
+/// Splits an event message into its code and body.
+pub fn decode_event(payload: &[u8]) -> Result<(NetworkEvent, &[u8]), EventError> {
+    let (&code, body) = payload.split_first().ok_or(EventError::Empty)?;
+    let code = NetworkEvent::from_repr(code).ok_or(EventError::UnknownCode(code))?;
+    Ok((code, body))
+}
+
+/// Sends `data` as event `E`, checked against what `M` allows at compile
+/// time, e.g.
+/// `send::<ConstEvent<{ NetworkEvent::ConfirmedKill as u8 }>, TeamDeathMatch>`.
+/// Fails with [`EventError::WrongDirection`] if `E` doesn't go towards
+/// `peer`'s end.
+pub fn send<E: TypedEvent<M>, M: GameMode>(
+    peer: &mut NetPeer,
+    data: &E::Data,
+    options: SendOptions,
+) -> Result<(), EventError> {
+    if !E::DIRECTION.accepted_by(peer.role().remote()) {
+        return Err(EventError::WrongDirection(E::CODE));
+    }
+    Ok(peer.send(&E::encode(data)?, options)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::{
+        events::{BattleArena, ConstEvent, ingame::PlayerId},
+        net::peer::tests::{deliver, pair},
+    };
+
+    type Invisible = ConstEvent<{ NetworkEvent::MakeInvisible as u8 }>;
+    type EnergyModule = ConstEvent<{ NetworkEvent::EnergyModuleActivated as u8 }>;
+
+    #[test]
+    fn send_checks_direction() {
+        let now = Instant::now();
+        let (mut server, mut client) = pair(now);
+        let player = PlayerId { player: 3 };
+        assert!(matches!(
+            send::<Invisible, BattleArena>(&mut client, &player, SendOptions::ReliableOrdered),
+            Err(EventError::WrongDirection(NetworkEvent::MakeInvisible))
+        ));
+        send::<Invisible, BattleArena>(&mut server, &player, SendOptions::ReliableOrdered).unwrap();
+        send::<EnergyModule, BattleArena>(&mut client, &player, SendOptions::ReliableOrdered)
+            .unwrap();
+        assert_eq!(deliver(&mut server, &mut client, now).len(), 1);
+        assert_eq!(deliver(&mut client, &mut server, now).len(), 1);
+    }
+}
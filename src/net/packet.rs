@@ -0,0 +1,697 @@
+use std::fmt;
+
+use strum::FromRepr;
+
+use crate::net::SendOptions;
+
+pub const HEADER_SIZE: usize = 1;
+pub const CHANNELED_HEADER_SIZE: usize = 4;
+pub const FRAGMENT_HEADER_SIZE: usize = 6;
+pub const MERGED_LENGTH_SIZE: usize = 2;
+pub const MAX_SEQUENCE: u16 = 32768;
+pub const HALF_MAX_SEQUENCE: u16 = MAX_SEQUENCE / 2;
+pub const PROTOCOL_ID: i32 = 11;
+
+const PROPERTY_MASK: u8 = 0x1F;
+const CONNECTION_NUMBER_MASK: u8 = 0x60;
+const CONNECTION_NUMBER_SHIFT: u8 = 5;
+const FRAGMENTED_BIT: u8 = 0x80;
+// LiteNetLib packs the delivery method into the low bits of the channel id,
+// with 4 delivery methods per channel number.
+const CHANNEL_TYPE_COUNT: u8 = 4;
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, FromRepr, PartialEq, Eq, Hash)]
+pub enum PacketProperty {
+    Unreliable = 0,
+    Channeled = 1,
+    Ack = 2,
+    Ping = 3,
+    Pong = 4,
+    ConnectRequest = 5,
+    ConnectAccept = 6,
+    Disconnect = 7,
+    UnconnectedMessage = 8,
+    MtuCheck = 9,
+    MtuOk = 10,
+    Broadcast = 11,
+    Merged = 12,
+    ShutdownOk = 13,
+    PeerNotFound = 14,
+    InvalidProtocol = 15,
+    NatMessage = 16,
+    Empty = 17,
+}
+
+impl PacketProperty {
+    /// Size of the fixed header for packets with this property, not counting
+    /// the fragment header.
+    pub fn header_size(self) -> usize {
+        match self {
+            PacketProperty::Channeled | PacketProperty::Ack => CHANNELED_HEADER_SIZE,
+            PacketProperty::Ping | PacketProperty::Pong => HEADER_SIZE + 2,
+            _ => HEADER_SIZE,
+        }
+    }
+    fn has_sequence(self) -> bool {
+        matches!(
+            self,
+            PacketProperty::Channeled
+                | PacketProperty::Ack
+                | PacketProperty::Ping
+                | PacketProperty::Pong
+        )
+    }
+    fn has_channel(self) -> bool {
+        matches!(self, PacketProperty::Channeled | PacketProperty::Ack)
+    }
+}
+
+/// LiteNetLib's `DeliveryMethod` as stored in the low bits of a channel id.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, FromRepr, PartialEq, Eq, Hash)]
+pub enum DeliveryMethod {
+    ReliableUnordered = 0,
+    Sequenced = 1,
+    ReliableOrdered = 2,
+    ReliableSequenced = 3,
+    Unreliable = 4,
+}
+
+impl From<SendOptions> for DeliveryMethod {
+    fn from(value: SendOptions) -> Self {
+        match value {
+            SendOptions::Unreliable => DeliveryMethod::Unreliable,
+            SendOptions::ReliableUnordered => DeliveryMethod::ReliableUnordered,
+            SendOptions::Sequenced => DeliveryMethod::Sequenced,
+            SendOptions::ReliableOrdered => DeliveryMethod::ReliableOrdered,
+        }
+    }
+}
+
+impl TryFrom<DeliveryMethod> for SendOptions {
+    type Error = PacketError;
+
+    fn try_from(value: DeliveryMethod) -> Result<Self, Self::Error> {
+        Ok(match value {
+            DeliveryMethod::Unreliable => SendOptions::Unreliable,
+            DeliveryMethod::ReliableUnordered => SendOptions::ReliableUnordered,
+            DeliveryMethod::Sequenced => SendOptions::Sequenced,
+            DeliveryMethod::ReliableOrdered => SendOptions::ReliableOrdered,
+            DeliveryMethod::ReliableSequenced => {
+                return Err(PacketError::UnsupportedDeliveryMethod(value));
+            }
+        })
+    }
+}
+
+pub fn channel_id(channel_number: u8, options: SendOptions) -> u8 {
+    channel_number * CHANNEL_TYPE_COUNT + DeliveryMethod::from(options) as u8
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketError {
+    Empty,
+    UnknownProperty(u8),
+    Truncated {
+        property: PacketProperty,
+        expected: usize,
+        actual: usize,
+    },
+    UnsupportedDeliveryMethod(DeliveryMethod),
+    InvalidChannel(u8),
+    InvalidMergedLength,
+    InvalidProtocol(i32),
+}
+
+impl fmt::Display for PacketError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PacketError::Empty => write!(f, "empty packet"),
+            PacketError::UnknownProperty(p) => write!(f, "unknown packet property {p}"),
+            PacketError::Truncated {
+                property,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{property:?} packet too short: expected at least {expected} bytes, got {actual}"
+            ),
+            PacketError::UnsupportedDeliveryMethod(m) => {
+                write!(f, "unsupported delivery method {m:?}")
+            }
+            PacketError::InvalidChannel(c) => write!(f, "invalid channel id {c}"),
+            PacketError::InvalidMergedLength => write!(f, "merged packet length out of bounds"),
+            PacketError::InvalidProtocol(id) => write!(f, "unsupported protocol id {id}"),
+        }
+    }
+}
+
+impl std::error::Error for PacketError {}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FragmentHeader {
+    pub fragment_id: u16,
+    pub fragment_part: u16,
+    pub fragments_total: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacketHeader {
+    pub property: PacketProperty,
+    pub connection_number: u8,
+    pub sequence: u16,
+    pub channel_id: u8,
+    pub fragment: Option<FragmentHeader>,
+}
+
+impl PacketHeader {
+    pub fn new(property: PacketProperty) -> Self {
+        Self {
+            property,
+            connection_number: 0,
+            sequence: 0,
+            channel_id: 0,
+            fragment: None,
+        }
+    }
+    pub fn channeled(options: SendOptions, sequence: u16) -> Self {
+        Self {
+            sequence,
+            channel_id: channel_id(0, options),
+            ..Self::new(PacketProperty::Channeled)
+        }
+    }
+    pub fn size(&self) -> usize {
+        self.property.header_size()
+            + if self.fragment.is_some() {
+                FRAGMENT_HEADER_SIZE
+            } else {
+                0
+            }
+    }
+    pub fn is_fragmented(&self) -> bool {
+        self.fragment.is_some()
+    }
+    pub fn is_merged(&self) -> bool {
+        self.property == PacketProperty::Merged
+    }
+    pub fn channel_number(&self) -> u8 {
+        self.channel_id / CHANNEL_TYPE_COUNT
+    }
+    pub fn delivery_method(&self) -> Result<DeliveryMethod, PacketError> {
+        match self.property {
+            PacketProperty::Unreliable => Ok(DeliveryMethod::Unreliable),
+            PacketProperty::Channeled | PacketProperty::Ack => {
+                DeliveryMethod::from_repr(self.channel_id % CHANNEL_TYPE_COUNT)
+                    .ok_or(PacketError::InvalidChannel(self.channel_id))
+            }
+            _ => Err(PacketError::InvalidChannel(self.channel_id)),
+        }
+    }
+    /// The [`SendOptions`] this packet was sent with, for packets carrying
+    /// user data.
+    pub fn send_options(&self) -> Result<SendOptions, PacketError> {
+        self.delivery_method()?.try_into()
+    }
+    pub fn write(&self, out: &mut Vec<u8>) {
+        let mut first = self.property as u8
+            | ((self.connection_number << CONNECTION_NUMBER_SHIFT) & CONNECTION_NUMBER_MASK);
+        if self.fragment.is_some() {
+            first |= FRAGMENTED_BIT;
+        }
+        out.push(first);
+        if self.property.has_sequence() {
+            out.extend_from_slice(&self.sequence.to_le_bytes());
+        }
+        if self.property.has_channel() {
+            out.push(self.channel_id);
+        }
+        if let Some(frag) = self.fragment {
+            out.extend_from_slice(&frag.fragment_id.to_le_bytes());
+            out.extend_from_slice(&frag.fragment_part.to_le_bytes());
+            out.extend_from_slice(&frag.fragments_total.to_le_bytes());
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Packet<'a> {
+    pub header: PacketHeader,
+    pub payload: &'a [u8],
+}
+
+impl<'a> Packet<'a> {
+    pub fn new(header: PacketHeader, payload: &'a [u8]) -> Self {
+        Self { header, payload }
+    }
+    pub fn parse(data: &'a [u8]) -> Result<Self, PacketError> {
+        let &first = data.first().ok_or(PacketError::Empty)?;
+        let property = PacketProperty::from_repr(first & PROPERTY_MASK)
+            .ok_or(PacketError::UnknownProperty(first & PROPERTY_MASK))?;
+        let fragmented = first & FRAGMENTED_BIT != 0;
+        let mut header = PacketHeader {
+            property,
+            connection_number: (first & CONNECTION_NUMBER_MASK) >> CONNECTION_NUMBER_SHIFT,
+            sequence: 0,
+            channel_id: 0,
+            fragment: fragmented.then(FragmentHeader::default),
+        };
+        let size = header.size();
+        if data.len() < size {
+            return Err(PacketError::Truncated {
+                property,
+                expected: size,
+                actual: data.len(),
+            });
+        }
+        let u16_at = |i: usize| u16::from_le_bytes([data[i], data[i + 1]]);
+        if property.has_sequence() {
+            header.sequence = u16_at(1);
+        }
+        if property.has_channel() {
+            header.channel_id = data[3];
+        }
+        if let Some(frag) = header.fragment.as_mut() {
+            let base = property.header_size();
+            frag.fragment_id = u16_at(base);
+            frag.fragment_part = u16_at(base + 2);
+            frag.fragments_total = u16_at(base + 4);
+        }
+        Ok(Self {
+            header,
+            payload: &data[size..],
+        })
+    }
+    /// Iterates the packets contained in a [`PacketProperty::Merged`] packet.
+    pub fn merged(&self) -> MergedPackets<'a> {
+        MergedPackets {
+            data: if self.header.is_merged() {
+                self.payload
+            } else {
+                &[]
+            },
+        }
+    }
+    pub fn write(&self, out: &mut Vec<u8>) {
+        self.header.write(out);
+        out.extend_from_slice(self.payload);
+    }
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.header.size() + self.payload.len());
+        self.write(&mut out);
+        out
+    }
+}
+
+/// Each merged packet is prefixed with its length as a little endian `u16`.
+#[derive(Debug, Clone)]
+pub struct MergedPackets<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for MergedPackets<'a> {
+    type Item = Result<Packet<'a>, PacketError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() {
+            return None;
+        }
+        let Some((len, rest)) = self.data.split_first_chunk::<MERGED_LENGTH_SIZE>() else {
+            self.data = &[];
+            return Some(Err(PacketError::InvalidMergedLength));
+        };
+        let len = usize::from(u16::from_le_bytes(*len));
+        if len == 0 || len > rest.len() {
+            self.data = &[];
+            return Some(Err(PacketError::InvalidMergedLength));
+        }
+        let (packet, rest) = rest.split_at(len);
+        self.data = rest;
+        Some(Packet::parse(packet))
+    }
+}
+
+/// Signed distance from `b` to `a` in LiteNetLib's wrapping sequence space.
+pub fn relative_sequence(a: u16, b: u16) -> i16 {
+    let diff =
+        (i32::from(a) - i32::from(b) + i32::from(MAX_SEQUENCE) + i32::from(HALF_MAX_SEQUENCE))
+            % i32::from(MAX_SEQUENCE)
+            - i32::from(HALF_MAX_SEQUENCE);
+    diff as i16
+}
+
+fn truncated(property: PacketProperty, expected: usize, payload: &[u8]) -> PacketError {
+    PacketError::Truncated {
+        property,
+        expected: property.header_size() + expected,
+        actual: property.header_size() + payload.len(),
+    }
+}
+
+const CONNECT_REQUEST_SIZE: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectRequestPacket<'a> {
+    pub protocol_id: i32,
+    pub connection_time: i64,
+    pub data: &'a [u8],
+}
+
+impl<'a> ConnectRequestPacket<'a> {
+    pub fn parse(payload: &'a [u8]) -> Result<Self, PacketError> {
+        let (fixed, data) = payload
+            .split_first_chunk::<CONNECT_REQUEST_SIZE>()
+            .ok_or_else(|| {
+                truncated(
+                    PacketProperty::ConnectRequest,
+                    CONNECT_REQUEST_SIZE,
+                    payload,
+                )
+            })?;
+        let protocol_id = i32::from_le_bytes(fixed[0..4].try_into().unwrap());
+        if protocol_id != PROTOCOL_ID {
+            return Err(PacketError::InvalidProtocol(protocol_id));
+        }
+        Ok(Self {
+            protocol_id,
+            connection_time: i64::from_le_bytes(fixed[4..12].try_into().unwrap()),
+            data,
+        })
+    }
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.protocol_id.to_le_bytes());
+        out.extend_from_slice(&self.connection_time.to_le_bytes());
+        out.extend_from_slice(self.data);
+    }
+}
+
+const CONNECT_ACCEPT_SIZE: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectAcceptPacket {
+    pub connection_time: i64,
+    pub connection_number: u8,
+    pub reused_peer: bool,
+}
+
+impl ConnectAcceptPacket {
+    pub fn parse(payload: &[u8]) -> Result<Self, PacketError> {
+        let (fixed, _) = payload
+            .split_first_chunk::<CONNECT_ACCEPT_SIZE>()
+            .ok_or_else(|| {
+                truncated(PacketProperty::ConnectAccept, CONNECT_ACCEPT_SIZE, payload)
+            })?;
+        Ok(Self {
+            connection_time: i64::from_le_bytes(fixed[0..8].try_into().unwrap()),
+            connection_number: fixed[8],
+            reused_peer: fixed[9] != 0,
+        })
+    }
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.connection_time.to_le_bytes());
+        out.push(self.connection_number);
+        out.push(self.reused_peer.into());
+    }
+}
+
+const DISCONNECT_SIZE: usize = 8;
+
+/// Sent both to close a connection and to reject a connection request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisconnectPacket<'a> {
+    pub connection_time: i64,
+    pub data: &'a [u8],
+}
+
+impl<'a> DisconnectPacket<'a> {
+    pub fn parse(payload: &'a [u8]) -> Result<Self, PacketError> {
+        let (fixed, data) = payload
+            .split_first_chunk::<DISCONNECT_SIZE>()
+            .ok_or_else(|| truncated(PacketProperty::Disconnect, DISCONNECT_SIZE, payload))?;
+        Ok(Self {
+            connection_time: i64::from_le_bytes(*fixed),
+            data,
+        })
+    }
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.connection_time.to_le_bytes());
+        out.extend_from_slice(self.data);
+    }
+}
+
+const PONG_SIZE: usize = 8;
+
+/// Reply to a ping, echoing its sequence in the header. The body carries the
+/// sender's clock in .NET ticks, which LiteNetLib uses for time sync.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PongPacket {
+    pub time: i64,
+}
+
+impl PongPacket {
+    pub fn parse(payload: &[u8]) -> Result<Self, PacketError> {
+        let (fixed, _) = payload
+            .split_first_chunk::<PONG_SIZE>()
+            .ok_or_else(|| truncated(PacketProperty::Pong, PONG_SIZE, payload))?;
+        Ok(Self {
+            time: i64::from_le_bytes(*fixed),
+        })
+    }
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.time.to_le_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(header: PacketHeader, payload: &[u8]) -> Vec<u8> {
+        let bytes = Packet::new(header, payload).to_vec();
+        let packet = Packet::parse(&bytes).unwrap();
+        assert_eq!(packet.header, header);
+        assert_eq!(packet.payload, payload);
+        bytes
+    }
+
+    #[test]
+    fn header_byte() {
+        let header = PacketHeader {
+            connection_number: 3,
+            ..PacketHeader::new(PacketProperty::Unreliable)
+        };
+        assert_eq!(round_trip(header, b"hi"), [0x60, b'h', b'i']);
+        let header = PacketHeader::new(PacketProperty::ShutdownOk);
+        assert_eq!(round_trip(header, &[]), [13]);
+        // Connection numbers only have two bits.
+        let header = PacketHeader {
+            connection_number: 5,
+            ..PacketHeader::new(PacketProperty::Disconnect)
+        };
+        let bytes = Packet::new(header, &[]).to_vec();
+        assert_eq!(Packet::parse(&bytes).unwrap().header.connection_number, 1);
+    }
+
+    #[test]
+    fn channeled_header() {
+        let header = PacketHeader::channeled(SendOptions::ReliableOrdered, 0x1234);
+        assert_eq!(round_trip(header, b"x"), [0x01, 0x34, 0x12, 2, b'x']);
+        assert_eq!(header.channel_number(), 0);
+        assert_eq!(
+            header.delivery_method(),
+            Ok(DeliveryMethod::ReliableOrdered)
+        );
+        assert_eq!(header.send_options(), Ok(SendOptions::ReliableOrdered));
+        let ack = PacketHeader {
+            channel_id: channel_id(1, SendOptions::Sequenced),
+            ..PacketHeader::new(PacketProperty::Ack)
+        };
+        round_trip(ack, &[0xFF; 8]);
+        assert_eq!(ack.channel_number(), 1);
+        assert_eq!(ack.send_options(), Ok(SendOptions::Sequenced));
+    }
+
+    #[test]
+    fn fragment_header() {
+        let fragment = FragmentHeader {
+            fragment_id: 7,
+            fragment_part: 2,
+            fragments_total: 3,
+        };
+        let header = PacketHeader {
+            fragment: Some(fragment),
+            ..PacketHeader::channeled(SendOptions::ReliableUnordered, 9)
+        };
+        assert!(header.is_fragmented());
+        assert_eq!(header.size(), CHANNELED_HEADER_SIZE + FRAGMENT_HEADER_SIZE);
+        let bytes = round_trip(header, b"part");
+        assert_eq!(bytes[..header.size()], [0x81, 9, 0, 0, 7, 0, 2, 0, 3, 0]);
+    }
+
+    #[test]
+    fn unsupported_channels() {
+        let header = PacketHeader {
+            channel_id: DeliveryMethod::ReliableSequenced as u8,
+            ..PacketHeader::new(PacketProperty::Channeled)
+        };
+        assert_eq!(
+            header.send_options(),
+            Err(PacketError::UnsupportedDeliveryMethod(
+                DeliveryMethod::ReliableSequenced
+            ))
+        );
+        let header = PacketHeader::new(PacketProperty::ConnectAccept);
+        assert_eq!(
+            header.delivery_method(),
+            Err(PacketError::InvalidChannel(0))
+        );
+    }
+
+    #[test]
+    fn merged_packets() {
+        let first = Packet::new(PacketHeader::new(PacketProperty::Unreliable), b"one").to_vec();
+        let second = Packet::new(
+            PacketHeader::channeled(SendOptions::ReliableOrdered, 1),
+            b"two",
+        )
+        .to_vec();
+        let mut payload = Vec::new();
+        for packet in [&first, &second] {
+            payload.extend_from_slice(&(packet.len() as u16).to_le_bytes());
+            payload.extend_from_slice(packet);
+        }
+        let merged = Packet::new(PacketHeader::new(PacketProperty::Merged), &payload);
+        let inner: Vec<_> = merged.merged().map(Result::unwrap).collect();
+        assert_eq!(inner.len(), 2);
+        assert_eq!(inner[0].payload, b"one");
+        assert_eq!(inner[1].header.sequence, 1);
+        assert_eq!(inner[1].payload, b"two");
+        // Only merged packets have anything to iterate.
+        let plain = Packet::new(PacketHeader::new(PacketProperty::Unreliable), &payload);
+        assert_eq!(plain.merged().count(), 0);
+    }
+
+    #[test]
+    fn malformed_merged_packets() {
+        let header = PacketHeader::new(PacketProperty::Merged);
+        for payload in [&[4][..], &[0, 0, 1], &[9, 0, 0, 1, 2]] {
+            let merged: Vec<_> = Packet::new(header, payload).merged().collect();
+            assert_eq!(merged, [Err(PacketError::InvalidMergedLength)]);
+        }
+        let merged: Vec<_> = Packet::new(header, &[1, 0, 0x1F]).merged().collect();
+        assert_eq!(merged, [Err(PacketError::UnknownProperty(0x1F))]);
+    }
+
+    #[test]
+    fn malformed_packets() {
+        assert_eq!(Packet::parse(&[]), Err(PacketError::Empty));
+        assert_eq!(
+            Packet::parse(&[0x12]),
+            Err(PacketError::UnknownProperty(0x12))
+        );
+        assert_eq!(
+            Packet::parse(&[0x01, 0, 0]),
+            Err(PacketError::Truncated {
+                property: PacketProperty::Channeled,
+                expected: CHANNELED_HEADER_SIZE,
+                actual: 3,
+            })
+        );
+        assert_eq!(
+            Packet::parse(&[0x81, 0, 0, 0, 1, 0]),
+            Err(PacketError::Truncated {
+                property: PacketProperty::Channeled,
+                expected: CHANNELED_HEADER_SIZE + FRAGMENT_HEADER_SIZE,
+                actual: 6,
+            })
+        );
+        // Every prefix of a valid packet either parses or fails cleanly.
+        let header = PacketHeader {
+            fragment: Some(FragmentHeader::default()),
+            ..PacketHeader::channeled(SendOptions::ReliableOrdered, 1)
+        };
+        let bytes = Packet::new(header, b"payload").to_vec();
+        for len in 0..bytes.len() {
+            let _ = Packet::parse(&bytes[..len]);
+        }
+    }
+
+    #[test]
+    fn relative_sequence_wraps() {
+        assert_eq!(relative_sequence(5, 3), 2);
+        assert_eq!(relative_sequence(3, 5), -2);
+        assert_eq!(relative_sequence(1, MAX_SEQUENCE - 1), 2);
+        assert_eq!(relative_sequence(MAX_SEQUENCE - 1, 1), -2);
+        assert_eq!(relative_sequence(HALF_MAX_SEQUENCE - 1, 0), 16383);
+        assert_eq!(relative_sequence(HALF_MAX_SEQUENCE, 0), -16384);
+    }
+
+    #[test]
+    fn connect_packets() {
+        let mut body = Vec::new();
+        let request = ConnectRequestPacket {
+            protocol_id: PROTOCOL_ID,
+            connection_time: -2,
+            data: b"token",
+        };
+        request.write(&mut body);
+        assert_eq!(body.len(), CONNECT_REQUEST_SIZE + 5);
+        assert_eq!(ConnectRequestPacket::parse(&body), Ok(request));
+        body[0] = 12;
+        assert_eq!(
+            ConnectRequestPacket::parse(&body),
+            Err(PacketError::InvalidProtocol(12))
+        );
+
+        let mut body = Vec::new();
+        let accept = ConnectAcceptPacket {
+            connection_time: 1 << 40,
+            connection_number: 2,
+            reused_peer: true,
+        };
+        accept.write(&mut body);
+        assert_eq!(body.len(), CONNECT_ACCEPT_SIZE);
+        assert_eq!(ConnectAcceptPacket::parse(&body), Ok(accept));
+
+        let mut body = Vec::new();
+        let disconnect = DisconnectPacket {
+            connection_time: 77,
+            data: b"bye",
+        };
+        disconnect.write(&mut body);
+        assert_eq!(DisconnectPacket::parse(&body), Ok(disconnect));
+    }
+
+    #[test]
+    fn truncated_connect_packets() {
+        assert_eq!(
+            ConnectRequestPacket::parse(&[11, 0, 0, 0]),
+            Err(PacketError::Truncated {
+                property: PacketProperty::ConnectRequest,
+                expected: HEADER_SIZE + CONNECT_REQUEST_SIZE,
+                actual: HEADER_SIZE + 4,
+            })
+        );
+        assert!(ConnectAcceptPacket::parse(&[0; CONNECT_ACCEPT_SIZE - 1]).is_err());
+        assert!(DisconnectPacket::parse(&[0; DISCONNECT_SIZE - 1]).is_err());
+    }
+
+    #[test]
+    fn pong_packet() {
+        let header = PacketHeader {
+            sequence: 513,
+            ..PacketHeader::new(PacketProperty::Pong)
+        };
+        let mut body = Vec::new();
+        PongPacket { time: 638_000_000 }.write(&mut body);
+        let bytes = round_trip(header, &body);
+        assert_eq!(bytes[..3], [0x04, 1, 2]);
+        let pong = Packet::parse(&bytes).unwrap();
+        assert_eq!(
+            PongPacket::parse(pong.payload),
+            Ok(PongPacket { time: 638_000_000 })
+        );
+        assert!(PongPacket::parse(&body[..PONG_SIZE - 1]).is_err());
+    }
+}
@@ -0,0 +1,794 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    net::SocketAddr,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use byteserde::prelude::ByteSerializeHeap;
+
+use crate::{
+    event_code::NetworkEvent,
+    events::Role,
+    net::{
+        SendError, SendOptions,
+        channel::{ChannelPacket, ReliableChannel, SequencedChannel},
+        fragment::{self, FragmentAssembler},
+        message::{EventError, decode_event, encode_event},
+        packet::{
+            CHANNELED_HEADER_SIZE, DisconnectPacket, FRAGMENT_HEADER_SIZE, HEADER_SIZE,
+            MAX_SEQUENCE, MERGED_LENGTH_SIZE, Packet, PacketHeader, PacketProperty, PongPacket,
+            relative_sequence,
+        },
+        rate_limit::PeerLimiter,
+        session::Join,
+        stats::PeerStats,
+    },
+    photon::enter_battle::NetworkConfig,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PeerId(pub(crate) u32);
+
+impl fmt::Display for PeerId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    Connecting,
+    Connected,
+    Disconnected,
+}
+
+/// Why a connection ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DisconnectReason {
+    /// Nothing was heard from the remote for
+    /// `max_milliseconds_delay_for_being_disconnected`.
+    Timeout,
+    /// The remote closed the connection.
+    RemoteClose,
+    /// The remote refused the connection request.
+    Rejected,
+    /// This side closed the connection.
+    Shutdown,
+}
+
+/// The parts of [`NetworkConfig`] each peer needs, resolved once per
+/// endpoint.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PeerSettings {
+    pub(crate) role: Role,
+    pub(crate) window_size: u16,
+    pub(crate) max_queue_size: usize,
+    pub(crate) resend_delay_base: Duration,
+    pub(crate) resend_delay_rtt_mult: f64,
+    pub(crate) ping_interval: Duration,
+    pub(crate) mtu: usize,
+    pub(crate) max_message_size: usize,
+    pub(crate) disconnect_timeout: Duration,
+    pub(crate) max_combined_count: usize,
+    pub(crate) max_combined_message_size: usize,
+}
+
+impl PeerSettings {
+    pub(crate) fn new(config: &NetworkConfig, role: Role) -> Self {
+        Self {
+            role,
+            // Long acks double the window, the same as UNET's IsAcksLong.
+            window_size: if config.is_acks_long { 64 } else { 32 },
+            max_queue_size: config.max_sent_message_queue_size.into(),
+            resend_delay_base: Duration::from_secs_f64(config.resend_delay_base.max(0.0) / 1000.0),
+            resend_delay_rtt_mult: config.resend_delay_rtt_mult.max(0.0),
+            ping_interval: Duration::from_millis(
+                config.network_peer_update_interval.max(0).unsigned_abs(),
+            ),
+            mtu: usize::from(config.packet_size)
+                .max(CHANNELED_HEADER_SIZE + FRAGMENT_HEADER_SIZE + 1),
+            max_message_size: config.max_packet_size.into(),
+            disconnect_timeout: Duration::from_millis(
+                config
+                    .max_milliseconds_delay_for_being_disconnected
+                    .max(0)
+                    .unsigned_abs()
+                    .into(),
+            ),
+            max_combined_count: config.max_combined_reliable_message_count.into(),
+            max_combined_message_size: config.max_combined_reliable_message_size.into(),
+        }
+    }
+    fn max_fragment_size(&self) -> usize {
+        self.mtu - CHANNELED_HEADER_SIZE - FRAGMENT_HEADER_SIZE
+    }
+}
+
+/// Datagrams waiting to be handed to the socket.
+///
+/// Small reliable packets are combined into [`PacketProperty::Merged`]
+/// datagrams, following UNET's `MaxCombinedReliableMessageCount` and
+/// `MaxCombinedReliableMessageSize`: packets up to the size limit are merged,
+/// up to the count limit per datagram and never beyond the MTU.
+#[derive(Debug)]
+pub(crate) struct OutgoingQueue {
+    connection_number: u8,
+    mtu: usize,
+    max_combined_count: usize,
+    max_combined_message_size: usize,
+    datagrams: VecDeque<Vec<u8>>,
+    merged: Vec<u8>,
+    merged_count: usize,
+}
+
+impl OutgoingQueue {
+    fn new(connection_number: u8, settings: &PeerSettings) -> Self {
+        Self {
+            connection_number,
+            mtu: settings.mtu,
+            max_combined_count: settings.max_combined_count,
+            max_combined_message_size: settings.max_combined_message_size,
+            datagrams: VecDeque::new(),
+            merged: Vec::new(),
+            merged_count: 0,
+        }
+    }
+    pub(crate) fn push(&mut self, mut header: PacketHeader, payload: &[u8]) {
+        header.connection_number = self.connection_number;
+        let packet = Packet::new(header, payload).to_vec();
+        let reliable = header.property == PacketProperty::Channeled
+            && matches!(
+                header.send_options(),
+                Ok(SendOptions::ReliableOrdered | SendOptions::ReliableUnordered)
+            );
+        if !reliable
+            || self.max_combined_count < 2
+            || payload.len() > self.max_combined_message_size
+        {
+            self.datagrams.push_back(packet);
+            return;
+        }
+        if self.merged_count == self.max_combined_count
+            || self.merged.len() + MERGED_LENGTH_SIZE + packet.len() > self.mtu
+        {
+            self.flush_merged();
+        }
+        if self.merged.is_empty() {
+            let header = PacketHeader {
+                connection_number: self.connection_number,
+                ..PacketHeader::new(PacketProperty::Merged)
+            };
+            header.write(&mut self.merged);
+        }
+        self.merged
+            .extend_from_slice(&(packet.len() as u16).to_le_bytes());
+        self.merged.extend_from_slice(&packet);
+        self.merged_count += 1;
+    }
+    fn flush_merged(&mut self) {
+        match self.merged_count {
+            0 => {}
+            // Not worth the merged header for a single packet.
+            1 => self
+                .datagrams
+                .push_back(self.merged[HEADER_SIZE + MERGED_LENGTH_SIZE..].to_vec()),
+            _ => self.datagrams.push_back(self.merged.clone()),
+        }
+        self.merged.clear();
+        self.merged_count = 0;
+    }
+}
+
+#[derive(Debug)]
+pub struct NetPeer {
+    id: PeerId,
+    addr: SocketAddr,
+    state: ConnectionState,
+    disconnect_reason: Option<DisconnectReason>,
+    last_received: Instant,
+    /// The clock of the latest poll that touched this peer, for closing
+    /// from outside a poll.
+    clock: Instant,
+    connection_time: i64,
+    connection_number: u8,
+    connect_data: Vec<u8>,
+    team: Option<i32>,
+    join: Option<Join>,
+    settings: PeerSettings,
+    reliable_unordered: ReliableChannel,
+    reliable_ordered: ReliableChannel,
+    sequenced: SequencedChannel,
+    next_fragment_id: u16,
+    fragments: FragmentAssembler,
+    outgoing: OutgoingQueue,
+    received: Vec<(Vec<u8>, SendOptions)>,
+    stats: PeerStats,
+    limiter: PeerLimiter,
+
+    ping_sequence: u16,
+    ping_sent: Option<Instant>,
+    ping_in_flight: bool,
+    remote_ping_sequence: Option<u16>,
+    rtt: Option<Duration>,
+    resend_delay: Duration,
+}
+
+impl NetPeer {
+    pub(crate) fn new(
+        id: PeerId,
+        addr: SocketAddr,
+        connection_time: i64,
+        connection_number: u8,
+        connect_data: Vec<u8>,
+        settings: PeerSettings,
+        now: Instant,
+    ) -> Self {
+        let reliable =
+            |options| ReliableChannel::new(options, settings.window_size, settings.max_queue_size);
+        Self {
+            id,
+            addr,
+            state: ConnectionState::Connecting,
+            disconnect_reason: None,
+            last_received: now,
+            clock: now,
+            connection_time,
+            connection_number,
+            connect_data,
+            team: None,
+            join: None,
+            settings,
+            reliable_unordered: reliable(SendOptions::ReliableUnordered),
+            reliable_ordered: reliable(SendOptions::ReliableOrdered),
+            sequenced: SequencedChannel::default(),
+            next_fragment_id: 0,
+            fragments: FragmentAssembler::new(
+                settings.max_message_size,
+                settings.max_fragment_size(),
+                settings.window_size,
+                // A live connection delivers every fragment long before it
+                // would time out, so this only bounds garbage from a broken
+                // sender.
+                settings.disconnect_timeout,
+            ),
+            outgoing: OutgoingQueue::new(connection_number, &settings),
+            received: Vec::new(),
+            stats: PeerStats::new(id),
+            limiter: PeerLimiter::default(),
+            ping_sequence: 0,
+            ping_sent: None,
+            ping_in_flight: false,
+            remote_ping_sequence: None,
+            rtt: None,
+            resend_delay: settings.resend_delay_base,
+        }
+    }
+    pub fn id(&self) -> PeerId {
+        self.id
+    }
+    /// Which side of the connection this end is: [`Role::Server`] for the
+    /// peers of a [`NetServer`](crate::net::NetServer).
+    pub fn role(&self) -> Role {
+        self.settings.role
+    }
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+    pub fn state(&self) -> ConnectionState {
+        self.state
+    }
+    pub fn is_connected(&self) -> bool {
+        self.state == ConnectionState::Connected
+    }
+    /// Set once the peer is [`ConnectionState::Disconnected`].
+    pub fn disconnect_reason(&self) -> Option<DisconnectReason> {
+        self.disconnect_reason
+    }
+    pub fn connection_time(&self) -> i64 {
+        self.connection_time
+    }
+    pub fn connection_number(&self) -> u8 {
+        self.connection_number
+    }
+    /// Extra data attached to the connect request.
+    pub fn connect_data(&self) -> &[u8] {
+        &self.connect_data
+    }
+    /// The team this peer plays on, for
+    /// [`Recipients::Team`](crate::net::Recipients::Team) broadcasts.
+    pub fn team(&self) -> Option<i32> {
+        self.team
+    }
+    pub fn set_team(&mut self, team: Option<i32>) {
+        self.team = team;
+    }
+    /// Which player this peer joined as, when the server tracks sessions.
+    pub fn join(&self) -> Option<Join> {
+        self.join
+    }
+    pub(crate) fn set_join(&mut self, join: Option<Join>) {
+        self.join = join;
+    }
+    /// Queues `payload` for delivery. Reliable payloads go out on the next
+    /// update; [`SendError::QueueFull`] means the channel already holds
+    /// `max_sent_message_queue_size` packets waiting for a window slot.
+    ///
+    /// Reliable payloads larger than one packet are fragmented, up to the
+    /// configured `max_packet_size`. Unreliable and sequenced payloads must
+    /// fit in a single packet.
+    pub fn send(&mut self, payload: &[u8], options: SendOptions) -> Result<(), SendError> {
+        self.send_message(payload, options)?;
+        if let Ok((code, _)) = decode_event(payload) {
+            self.stats.record_event_sent(code, payload.len());
+        }
+        Ok(())
+    }
+    fn send_message(&mut self, payload: &[u8], options: SendOptions) -> Result<(), SendError> {
+        if self.state != ConnectionState::Connected {
+            return Err(SendError::NotConnected);
+        }
+        if payload.len() > self.settings.max_message_size {
+            return Err(SendError::TooLarge);
+        }
+        let max_unfragmented = match options {
+            SendOptions::Unreliable => self.settings.mtu - HEADER_SIZE,
+            _ => self.settings.mtu - CHANNELED_HEADER_SIZE,
+        };
+        let channel = match options {
+            SendOptions::ReliableUnordered => &mut self.reliable_unordered,
+            SendOptions::ReliableOrdered => &mut self.reliable_ordered,
+            _ if payload.len() > max_unfragmented => return Err(SendError::TooLarge),
+            SendOptions::Unreliable => {
+                self.outgoing
+                    .push(PacketHeader::new(PacketProperty::Unreliable), payload);
+                return Ok(());
+            }
+            SendOptions::Sequenced => {
+                self.sequenced.send(payload, &mut self.outgoing);
+                return Ok(());
+            }
+        };
+        let packets = fragment::split(
+            payload,
+            max_unfragmented,
+            self.settings.max_fragment_size(),
+            self.next_fragment_id,
+        );
+        if packets.len() > 1 {
+            self.next_fragment_id = self.next_fragment_id.wrapping_add(1);
+        }
+        channel.send(packets)
+    }
+
+    /// Number of sequenced packets thrown away because a newer one had
+    /// already been delivered.
+    pub fn stale_packets_dropped(&self) -> u64 {
+        self.sequenced.dropped_stale()
+    }
+
+    /// A snapshot of this connection's traffic so far.
+    pub fn stats(&self) -> PeerStats {
+        let reliable = [&self.reliable_unordered, &self.reliable_ordered];
+        let resends: u64 = reliable.iter().map(|c| c.resends()).sum();
+        let sent: u64 = reliable.iter().map(|c| c.packets_sent()).sum();
+        PeerStats {
+            resends,
+            packet_loss: if sent == 0 {
+                0.0
+            } else {
+                resends as f64 * 100.0 / sent as f64
+            },
+            rtt: self.rtt,
+            ..self.stats.clone()
+        }
+    }
+    /// Smoothed round trip time, measured by pinging every
+    /// `network_peer_update_interval`. `None` until the first pong arrives.
+    pub fn rtt(&self) -> Option<Duration> {
+        self.rtt
+    }
+    /// Current reliable resend delay: `resend_delay_base` plus the smoothed
+    /// RTT scaled by `resend_delay_rtt_mult`.
+    pub fn resend_delay(&self) -> Duration {
+        self.resend_delay
+    }
+
+    /// Encodes and sends an event message, see [`encode_event`]. Events the
+    /// other end isn't meant to receive are refused with
+    /// [`EventError::WrongDirection`].
+    pub fn send_event(
+        &mut self,
+        code: NetworkEvent,
+        data: &impl ByteSerializeHeap,
+        options: SendOptions,
+    ) -> Result<(), EventError> {
+        if !code.accepted_by(self.role().remote()) {
+            return Err(EventError::WrongDirection(code));
+        }
+        let payload = encode_event(code, data)?;
+        Ok(self.send(&payload, options)?)
+    }
+
+    /// Sends a disconnect if the connection is up and marks the peer closed
+    /// from this side. Reliable packets already queued go out first, so a
+    /// last message such as a warning precedes the disconnect.
+    pub(crate) fn close(&mut self, now: Instant) {
+        if self.is_connected() {
+            self.send_channels(now);
+            self.outgoing.flush_merged();
+            let mut body = Vec::new();
+            DisconnectPacket {
+                connection_time: self.connection_time,
+                data: &[],
+            }
+            .write(&mut body);
+            self.send_raw(PacketHeader::new(PacketProperty::Disconnect), &body);
+        }
+        self.set_disconnected(DisconnectReason::Shutdown);
+    }
+    pub(crate) fn clock(&self) -> Instant {
+        self.clock
+    }
+    pub(crate) fn limiter_mut(&mut self) -> &mut PeerLimiter {
+        &mut self.limiter
+    }
+    pub(crate) fn set_connected(&mut self, now: Instant) {
+        self.state = ConnectionState::Connected;
+        self.last_received = now;
+    }
+    /// Marks the peer disconnected. The first reason given sticks.
+    pub(crate) fn set_disconnected(&mut self, reason: DisconnectReason) {
+        self.state = ConnectionState::Disconnected;
+        self.disconnect_reason.get_or_insert(reason);
+    }
+    pub(crate) fn send_raw(&mut self, header: PacketHeader, payload: &[u8]) {
+        self.outgoing.push(header, payload);
+    }
+    /// Handles a packet carrying user data or channel bookkeeping. Connection
+    /// management packets are handled by the owning server or client.
+    pub(crate) fn process_packet(&mut self, packet: Packet<'_>, now: Instant) {
+        self.last_received = now;
+        self.clock = now;
+        if packet.header.is_merged() {
+            for inner in packet.merged() {
+                match inner {
+                    Ok(inner) if !inner.header.is_merged() => self.process_packet(inner, now),
+                    Ok(_) => {}
+                    Err(_) => break,
+                }
+            }
+            return;
+        }
+        match packet.header.property {
+            PacketProperty::Ping => return self.process_ping(packet),
+            PacketProperty::Pong => return self.process_pong(packet, now),
+            _ => {}
+        }
+        let options = match packet.header.property {
+            PacketProperty::Unreliable => SendOptions::Unreliable,
+            PacketProperty::Channeled | PacketProperty::Ack
+                if packet.header.channel_number() == 0 =>
+            {
+                let Ok(options) = packet.header.send_options() else {
+                    return;
+                };
+                options
+            }
+            _ => return,
+        };
+        if packet.header.property == PacketProperty::Ack {
+            // Only reliable channels are acked, so any other ack is bogus.
+            if matches!(options, SendOptions::Unreliable | SendOptions::Sequenced) {
+                return;
+            }
+        } else {
+            let channel = self.stats.channel_mut(options);
+            channel.packets_received += 1;
+            channel.bytes_received += (packet.header.size() + packet.payload.len()) as u64;
+        }
+        let mut delivered = Vec::new();
+        match options {
+            SendOptions::Unreliable => delivered.push(ChannelPacket::from(packet)),
+            SendOptions::ReliableUnordered => self
+                .reliable_unordered
+                .process_packet(packet, &mut delivered),
+            SendOptions::ReliableOrdered => {
+                self.reliable_ordered.process_packet(packet, &mut delivered)
+            }
+            SendOptions::Sequenced => self.sequenced.process_packet(packet, &mut delivered),
+        }
+        for packet in delivered {
+            let payload = match packet.fragment {
+                Some(header) => {
+                    let Some(message) = self.fragments.insert(header, packet.payload, now) else {
+                        continue;
+                    };
+                    message
+                }
+                None => packet.payload,
+            };
+            if let Ok((code, _)) = decode_event(&payload) {
+                self.stats.record_event_received(code, payload.len());
+            }
+            self.received.push((payload, options));
+        }
+    }
+    fn process_ping(&mut self, packet: Packet<'_>) {
+        let seq = packet.header.sequence;
+        // A reordered ping would only produce a useless pong.
+        if self
+            .remote_ping_sequence
+            .is_some_and(|last| relative_sequence(seq, last) <= 0)
+        {
+            return;
+        }
+        self.remote_ping_sequence = Some(seq);
+        let mut body = Vec::new();
+        PongPacket {
+            time: dotnet_ticks(),
+        }
+        .write(&mut body);
+        let header = PacketHeader {
+            sequence: seq,
+            ..PacketHeader::new(PacketProperty::Pong)
+        };
+        self.outgoing.push(header, &body);
+    }
+    fn process_pong(&mut self, packet: Packet<'_>, now: Instant) {
+        if !self.ping_in_flight
+            || packet.header.sequence != self.ping_sequence
+            || PongPacket::parse(packet.payload).is_err()
+        {
+            return;
+        }
+        let Some(sent) = self.ping_sent else {
+            return;
+        };
+        self.ping_in_flight = false;
+        let sample = now.duration_since(sent);
+        // Same smoothing as TCP's SRTT (RFC 6298), so one slow pong doesn't
+        // stall every resend.
+        let rtt = match self.rtt {
+            Some(rtt) => (rtt * 7 + sample) / 8,
+            None => sample,
+        };
+        self.rtt = Some(rtt);
+        self.resend_delay =
+            self.settings.resend_delay_base + rtt.mul_f64(self.settings.resend_delay_rtt_mult);
+    }
+    /// Runs the channels' send logic and keeps pinging, producing datagrams
+    /// for the socket. Times the peer out if it has gone quiet.
+    pub(crate) fn update(&mut self, now: Instant) {
+        self.clock = now;
+        if self.state == ConnectionState::Connected
+            && now.duration_since(self.last_received) > self.settings.disconnect_timeout
+        {
+            self.set_disconnected(DisconnectReason::Timeout);
+            return;
+        }
+        self.fragments.expire(now);
+        let ping_due = self
+            .ping_sent
+            .is_none_or(|sent| now.duration_since(sent) >= self.settings.ping_interval);
+        if self.state == ConnectionState::Connected && ping_due {
+            // An unanswered ping is simply replaced; its pong would be stale.
+            self.ping_sequence = (self.ping_sequence + 1) % MAX_SEQUENCE;
+            self.ping_sent = Some(now);
+            self.ping_in_flight = true;
+            let header = PacketHeader {
+                sequence: self.ping_sequence,
+                ..PacketHeader::new(PacketProperty::Ping)
+            };
+            self.outgoing.push(header, &[]);
+        }
+        self.send_channels(now);
+    }
+    fn send_channels(&mut self, now: Instant) {
+        let resend_delay = self.resend_delay;
+        self.reliable_unordered
+            .send_next_packets(now, resend_delay, &mut self.outgoing);
+        self.reliable_ordered
+            .send_next_packets(now, resend_delay, &mut self.outgoing);
+    }
+    pub(crate) fn take_received(&mut self) -> Vec<(Vec<u8>, SendOptions)> {
+        std::mem::take(&mut self.received)
+    }
+    pub(crate) fn drain_outgoing(&mut self) -> impl Iterator<Item = Vec<u8>> + '_ {
+        self.outgoing.flush_merged();
+        for datagram in &self.outgoing.datagrams {
+            let Ok(packet) = Packet::parse(datagram) else {
+                continue;
+            };
+            if packet.header.is_merged() {
+                for inner in packet.merged().flatten() {
+                    record_sent(&mut self.stats, inner);
+                }
+            } else {
+                record_sent(&mut self.stats, packet);
+            }
+        }
+        self.outgoing.datagrams.drain(..)
+    }
+}
+
+fn record_sent(stats: &mut PeerStats, packet: Packet<'_>) {
+    let options = match packet.header.property {
+        PacketProperty::Unreliable => SendOptions::Unreliable,
+        PacketProperty::Channeled => match packet.header.send_options() {
+            Ok(options) => options,
+            Err(_) => return,
+        },
+        _ => return,
+    };
+    let channel = stats.channel_mut(options);
+    channel.packets_sent += 1;
+    channel.bytes_sent += (packet.header.size() + packet.payload.len()) as u64;
+}
+
+// .NET ticks (100ns since 0001-01-01) at the Unix epoch.
+const UNIX_EPOCH_TICKS: i64 = 621_355_968_000_000_000;
+
+pub(crate) fn dotnet_ticks() -> i64 {
+    let since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    UNIX_EPOCH_TICKS + (since_epoch.as_nanos() / 100) as i64
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::events::ingame::Kill;
+
+    pub(crate) fn config() -> NetworkConfig {
+        NetworkConfig {
+            network_channel_type: "ReliableSequenced".into(),
+            max_sent_message_queue_size: 128,
+            is_acks_long: true,
+            network_drop_threshold: 50,
+            packet_size: 1400,
+            max_combined_reliable_message_count: 10,
+            max_combined_reliable_message_size: 100,
+            min_update_timeout: 10,
+            max_delay: 10,
+            overflow_threshold: 10,
+            max_packet_size: 20000,
+            resend_delay_base: 25.0,
+            resend_delay_rtt_mult: 2.1,
+            network_peer_update_interval: 1000,
+            max_milliseconds_delay_for_being_disconnected: 5000,
+        }
+    }
+
+    /// A server-side and a client-side peer, connected to each other.
+    pub(crate) fn pair(now: Instant) -> (NetPeer, NetPeer) {
+        let config = config();
+        let peer = |id, port, role| {
+            let addr = SocketAddr::from(([127, 0, 0, 1], port));
+            let mut peer = NetPeer::new(
+                PeerId(id),
+                addr,
+                1,
+                0,
+                Vec::new(),
+                PeerSettings::new(&config, role),
+                now,
+            );
+            peer.set_connected(now);
+            peer
+        };
+        (peer(0, 1, Role::Server), peer(1, 2, Role::Client))
+    }
+
+    /// Runs an update on `from` and hands everything it sends to `to`,
+    /// returning what `to` received.
+    pub(crate) fn deliver(
+        from: &mut NetPeer,
+        to: &mut NetPeer,
+        now: Instant,
+    ) -> Vec<(Vec<u8>, SendOptions)> {
+        from.update(now);
+        for datagram in from.drain_outgoing().collect::<Vec<_>>() {
+            to.process_packet(Packet::parse(&datagram).unwrap(), now);
+        }
+        to.take_received()
+    }
+
+    #[test]
+    fn send_event_checks_direction() {
+        let (mut server, mut client) = pair(Instant::now());
+        let kill = Kill {
+            killee_player_id: 1,
+            killer_player_id: 2,
+        };
+        assert!(matches!(
+            server.send_event(
+                NetworkEvent::DamageCube,
+                &kill,
+                SendOptions::ReliableOrdered
+            ),
+            Err(EventError::WrongDirection(NetworkEvent::DamageCube))
+        ));
+        assert!(matches!(
+            client.send_event(
+                NetworkEvent::ConfirmedKill,
+                &kill,
+                SendOptions::ReliableOrdered
+            ),
+            Err(EventError::WrongDirection(NetworkEvent::ConfirmedKill))
+        ));
+        server
+            .send_event(
+                NetworkEvent::ConfirmedKill,
+                &kill,
+                SendOptions::ReliableOrdered,
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn small_reliable_packets_are_merged() {
+        let now = Instant::now();
+        let (mut server, mut client) = pair(now);
+        let messages: Vec<Vec<u8>> = (0..25u8).map(|i| vec![i; 5]).collect();
+        for message in &messages {
+            server.send(message, SendOptions::ReliableOrdered).unwrap();
+        }
+        // Too large to merge, sent on its own.
+        server
+            .send(&[0xAB; 200], SendOptions::ReliableOrdered)
+            .unwrap();
+        server.update(now);
+        let datagrams: Vec<_> = server.drain_outgoing().collect();
+        let merged: Vec<_> = datagrams
+            .iter()
+            .map(|datagram| Packet::parse(datagram).unwrap())
+            .filter(|packet| packet.header.is_merged())
+            .map(|packet| packet.merged().count())
+            .collect();
+        assert_eq!(merged, [10, 10, 5]);
+        for datagram in &datagrams {
+            client.process_packet(Packet::parse(datagram).unwrap(), now);
+        }
+        let received: Vec<_> = client
+            .take_received()
+            .into_iter()
+            .map(|(payload, _)| payload)
+            .collect();
+        assert_eq!(received[..25], messages[..]);
+        assert_eq!(received[25], [0xAB; 200]);
+    }
+
+    #[test]
+    fn rtt_converges_and_sets_the_resend_delay() {
+        let (mut server, mut client) = pair(Instant::now());
+        let settings = server.settings;
+        assert_eq!(server.rtt(), None);
+        assert_eq!(server.resend_delay(), settings.resend_delay_base);
+        let mut now = Instant::now();
+        for rtt in [200, 200, 200].into_iter().chain([40; 40]) {
+            deliver(&mut server, &mut client, now);
+            deliver(&mut client, &mut server, now + Duration::from_millis(rtt));
+            now += settings.ping_interval;
+        }
+        let rtt = server.rtt().unwrap();
+        assert!(rtt.abs_diff(Duration::from_millis(40)) < Duration::from_millis(1));
+        assert_eq!(
+            server.resend_delay(),
+            settings.resend_delay_base + rtt.mul_f64(settings.resend_delay_rtt_mult)
+        );
+    }
+
+    #[test]
+    fn silent_peers_time_out() {
+        let now = Instant::now();
+        let (mut server, _client) = pair(now);
+        let timeout = server.settings.disconnect_timeout;
+        server.update(now + timeout);
+        assert!(server.is_connected());
+        server.update(now + timeout + Duration::from_millis(1));
+        assert_eq!(server.state(), ConnectionState::Disconnected);
+        assert_eq!(server.disconnect_reason(), Some(DisconnectReason::Timeout));
+    }
+}
@@ -0,0 +1,279 @@
+use std::{collections::HashMap, time::Instant};
+
+use crate::{event_code::NetworkEvent, net::decode_event, types::StringCode};
+
+/// A token bucket: up to `burst` at once, refilling at `per_second`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub burst: f64,
+    pub per_second: f64,
+}
+
+impl RateLimit {
+    pub fn new(burst: f64, per_second: f64) -> Self {
+        Self { burst, per_second }
+    }
+}
+
+/// What happens to a peer that goes over a limit. The offending message is
+/// dropped in every case.
+#[derive(Debug, Clone)]
+pub enum LimitAction {
+    Drop,
+    /// Also sends the peer a `WarnPlayer` event, once per burst of
+    /// violations.
+    Warn(StringCode),
+    Disconnect,
+}
+
+/// Limits on what each peer may send, checked before messages reach the
+/// listener.
+#[derive(Debug, Clone)]
+pub struct RateLimits {
+    /// Messages per [`NetworkEvent`]. Events without an entry are only
+    /// subject to the byte budgets.
+    pub events: HashMap<NetworkEvent, RateLimit>,
+    /// Payload bytes across all messages from one peer.
+    pub bytes: Option<RateLimit>,
+    /// Payload bytes across all messages from every peer, so a flood spread
+    /// over many connections is capped too. Going over it drops the message
+    /// without counting against the sender, which may not be the one
+    /// flooding.
+    pub total_bytes: Option<RateLimit>,
+    pub action: LimitAction,
+}
+
+impl RateLimits {
+    pub fn new(action: LimitAction) -> Self {
+        Self {
+            events: HashMap::new(),
+            bytes: None,
+            total_bytes: None,
+            action,
+        }
+    }
+    pub fn with_event(mut self, code: NetworkEvent, limit: RateLimit) -> Self {
+        self.events.insert(code, limit);
+        self
+    }
+    pub fn with_bytes(mut self, limit: RateLimit) -> Self {
+        self.bytes = Some(limit);
+        self
+    }
+    pub fn with_total_bytes(mut self, limit: RateLimit) -> Self {
+        self.total_bytes = Some(limit);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn full(limit: RateLimit, now: Instant) -> Self {
+        Self {
+            tokens: limit.burst,
+            updated: now,
+        }
+    }
+    fn take(&mut self, limit: RateLimit, cost: f64, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.per_second).min(limit.burst);
+        self.updated = now;
+        if self.tokens < cost {
+            return false;
+        }
+        self.tokens -= cost;
+        true
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Verdict {
+    Allow,
+    /// Over a limit; `first` is set for the first violation since the peer
+    /// was last within its limits.
+    Violation {
+        first: bool,
+    },
+    /// Over the server-wide budget.
+    Overloaded,
+}
+
+/// The server-wide buckets, shared by every peer.
+#[derive(Debug, Default)]
+pub(crate) struct ServerLimiter {
+    bytes: Option<Bucket>,
+}
+
+/// One peer's buckets.
+#[derive(Debug, Default)]
+pub(crate) struct PeerLimiter {
+    events: HashMap<NetworkEvent, Bucket>,
+    bytes: Option<Bucket>,
+    violating: bool,
+}
+
+impl PeerLimiter {
+    pub(crate) fn check(
+        &mut self,
+        limits: &RateLimits,
+        server: &mut ServerLimiter,
+        payload: &[u8],
+        now: Instant,
+    ) -> Verdict {
+        // Byte budgets are only charged for messages the event limit lets
+        // through.
+        let event_ok = match decode_event(payload) {
+            Ok((code, _)) => limits.events.get(&code).is_none_or(|&limit| {
+                self.events
+                    .entry(code)
+                    .or_insert_with(|| Bucket::full(limit, now))
+                    .take(limit, 1.0, now)
+            }),
+            Err(_) => true,
+        };
+        let cost = payload.len() as f64;
+        let allowed = event_ok
+            && limits.bytes.is_none_or(|limit| {
+                self.bytes
+                    .get_or_insert_with(|| Bucket::full(limit, now))
+                    .take(limit, cost, now)
+            });
+        if !allowed {
+            let first = !self.violating;
+            self.violating = true;
+            return Verdict::Violation { first };
+        }
+        self.violating = false;
+        let within_total = limits.total_bytes.is_none_or(|limit| {
+            server
+                .bytes
+                .get_or_insert_with(|| Bucket::full(limit, now))
+                .take(limit, cost, now)
+        });
+        if within_total {
+            Verdict::Allow
+        } else {
+            Verdict::Overloaded
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    const PING: [u8; 1] = [NetworkEvent::MapPingEvent as u8];
+
+    fn check(
+        limiter: &mut PeerLimiter,
+        limits: &RateLimits,
+        payload: &[u8],
+        at: Instant,
+    ) -> Verdict {
+        limiter.check(limits, &mut ServerLimiter::default(), payload, at)
+    }
+
+    #[test]
+    fn buckets_refill_up_to_the_burst() {
+        let limit = RateLimit::new(2.0, 4.0);
+        let now = Instant::now();
+        let mut bucket = Bucket::full(limit, now);
+        assert!(bucket.take(limit, 2.0, now));
+        assert!(!bucket.take(limit, 1.0, now));
+        assert!(bucket.take(limit, 1.0, now + Duration::from_millis(250)));
+        // A long wait only refills as far as the burst.
+        let later = now + Duration::from_secs(10);
+        assert!(bucket.take(limit, 2.0, later));
+        assert!(!bucket.take(limit, 1.0, later));
+    }
+
+    #[test]
+    fn limits_each_event_separately() {
+        let limits = RateLimits::new(LimitAction::Drop)
+            .with_event(NetworkEvent::MapPingEvent, RateLimit::new(2.0, 1.0));
+        let mut limiter = PeerLimiter::default();
+        let now = Instant::now();
+        assert_eq!(check(&mut limiter, &limits, &PING, now), Verdict::Allow);
+        assert_eq!(check(&mut limiter, &limits, &PING, now), Verdict::Allow);
+        assert_eq!(
+            check(&mut limiter, &limits, &PING, now),
+            Verdict::Violation { first: true }
+        );
+        // Other events and non-event payloads aren't limited.
+        let taunt = [NetworkEvent::Taunt as u8];
+        assert_eq!(check(&mut limiter, &limits, &taunt, now), Verdict::Allow);
+        assert_eq!(check(&mut limiter, &limits, &[], now), Verdict::Allow);
+    }
+
+    #[test]
+    fn flags_the_first_violation_of_each_streak() {
+        let limits = RateLimits::new(LimitAction::Drop)
+            .with_event(NetworkEvent::MapPingEvent, RateLimit::new(1.0, 1.0));
+        let mut limiter = PeerLimiter::default();
+        let now = Instant::now();
+        let verdicts: Vec<_> = [0, 0, 0, 1000, 1000]
+            .map(|ms| {
+                check(
+                    &mut limiter,
+                    &limits,
+                    &PING,
+                    now + Duration::from_millis(ms),
+                )
+            })
+            .into();
+        assert_eq!(
+            verdicts,
+            [
+                Verdict::Allow,
+                Verdict::Violation { first: true },
+                Verdict::Violation { first: false },
+                Verdict::Allow,
+                Verdict::Violation { first: true },
+            ]
+        );
+    }
+
+    #[test]
+    fn byte_budget_covers_every_message_from_a_peer() {
+        let limits = RateLimits::new(LimitAction::Drop).with_bytes(RateLimit::new(10.0, 0.0));
+        let mut limiter = PeerLimiter::default();
+        let now = Instant::now();
+        assert_eq!(check(&mut limiter, &limits, &[0; 6], now), Verdict::Allow);
+        assert_eq!(
+            check(&mut limiter, &limits, &[0; 6], now),
+            Verdict::Violation { first: true }
+        );
+        assert_eq!(check(&mut limiter, &limits, &[0; 4], now), Verdict::Allow);
+    }
+
+    #[test]
+    fn total_byte_budget_is_shared_by_every_peer() {
+        let limits = RateLimits::new(LimitAction::Disconnect)
+            .with_bytes(RateLimit::new(10.0, 0.0))
+            .with_total_bytes(RateLimit::new(15.0, 0.0));
+        let mut server = ServerLimiter::default();
+        let mut peers = [PeerLimiter::default(), PeerLimiter::default()];
+        let now = Instant::now();
+        let [first, second] = &mut peers;
+        assert_eq!(
+            first.check(&limits, &mut server, &[0; 8], now),
+            Verdict::Allow
+        );
+        // Within its own budget, but not the server's.
+        assert_eq!(
+            second.check(&limits, &mut server, &[0; 8], now),
+            Verdict::Overloaded
+        );
+        assert_eq!(
+            second.check(&limits, &mut server, &[0; 2], now),
+            Verdict::Allow
+        );
+    }
+}
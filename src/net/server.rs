@@ -0,0 +1,935 @@
+use std::{
+    collections::HashMap,
+    fmt, io,
+    net::{SocketAddr, ToSocketAddrs},
+    time::Instant,
+};
+
+use byteserde::prelude::ByteSerializeHeap;
+
+use crate::{
+    event_code::NetworkEvent,
+    events::{Role, ingame::PlayerId},
+    net::{
+        EventError, GameGuidValidator, NetEventListener, SendError, SendOptions, decode_event,
+        discovery::{DISCOVERY_REQUEST, DiscoveryResponse, ServerInfo},
+        encode_event,
+        packet::{
+            ConnectAcceptPacket, ConnectRequestPacket, DisconnectPacket, Packet, PacketError,
+            PacketHeader, PacketProperty,
+        },
+        peer::{ConnectionState, DisconnectReason, NetPeer, PeerId, PeerSettings},
+        rate_limit::{LimitAction, RateLimits, ServerLimiter, Verdict},
+        session::{Join, ReconnectToken, SessionRegistry},
+        stats::PeerStats,
+        transport::{Transport, UdpTransport},
+    },
+    photon::enter_battle::NetworkConfig,
+};
+
+pub(crate) const MAX_DATAGRAM_SIZE: usize = u16::MAX as usize;
+
+/// Which connected peers a broadcast goes to.
+#[derive(Clone, Copy)]
+pub enum Recipients<'a> {
+    All,
+    Team(i32),
+    Except(PeerId),
+    Matching(&'a (dyn Fn(&NetPeer) -> bool + Sync)),
+}
+
+impl fmt::Debug for Recipients<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Recipients::All => f.write_str("All"),
+            Recipients::Team(team) => f.debug_tuple("Team").field(team).finish(),
+            Recipients::Except(id) => f.debug_tuple("Except").field(id).finish(),
+            Recipients::Matching(_) => f.write_str("Matching(..)"),
+        }
+    }
+}
+
+impl Recipients<'_> {
+    pub fn includes(&self, peer: &NetPeer) -> bool {
+        match self {
+            Recipients::All => true,
+            Recipients::Team(team) => peer.team() == Some(*team),
+            Recipients::Except(id) => peer.id() != *id,
+            Recipients::Matching(predicate) => predicate(peer),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConnectionRequest {
+    addr: SocketAddr,
+    connection_time: i64,
+    data: Vec<u8>,
+}
+
+impl ConnectionRequest {
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+    pub fn connection_time(&self) -> i64 {
+        self.connection_time
+    }
+    /// Extra data the client attached to its connect request.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+pub struct NetServer<T = UdpTransport> {
+    transport: T,
+    config: NetworkConfig,
+    settings: PeerSettings,
+    peers: HashMap<SocketAddr, NetPeer>,
+    peer_addrs: HashMap<PeerId, SocketAddr>,
+    next_peer_id: u32,
+    rate_limits: Option<RateLimits>,
+    limiter: ServerLimiter,
+    discovery: Option<ServerInfo>,
+    game_guid: Option<GameGuidValidator>,
+    sessions: Option<SessionRegistry>,
+    recv_buf: Vec<u8>,
+}
+
+impl NetServer {
+    /// Binds a UDP server to `addr`.
+    pub fn bind(addr: impl ToSocketAddrs, config: NetworkConfig) -> io::Result<Self> {
+        Ok(Self::new(UdpTransport::bind(addr)?, config))
+    }
+}
+
+impl<T: Transport> NetServer<T> {
+    pub fn new(transport: T, config: NetworkConfig) -> Self {
+        Self {
+            transport,
+            settings: PeerSettings::new(&config, Role::Server),
+            config,
+            peers: HashMap::new(),
+            peer_addrs: HashMap::new(),
+            next_peer_id: 0,
+            rate_limits: None,
+            limiter: ServerLimiter::default(),
+            discovery: None,
+            game_guid: None,
+            sessions: None,
+            recv_buf: vec![0; MAX_DATAGRAM_SIZE],
+        }
+    }
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.transport.local_addr()
+    }
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+    pub fn config(&self) -> &NetworkConfig {
+        &self.config
+    }
+    /// Limits applied to messages from every peer, or `None` to accept
+    /// everything.
+    pub fn set_rate_limits(&mut self, limits: Option<RateLimits>) {
+        self.rate_limits = limits;
+        self.limiter = ServerLimiter::default();
+    }
+    /// Answers LAN discovery requests with `info` and the current player
+    /// count. `None` stops answering.
+    pub fn set_discovery(&mut self, info: Option<ServerInfo>) {
+        self.discovery = info;
+    }
+    /// Requires every peer to pass `validator` before its events reach the
+    /// listener, or `None` to let everyone through. A successful
+    /// `ValidateGameGuid` is passed on so the listener knows the peer is in.
+    pub fn set_game_guid(&mut self, validator: Option<GameGuidValidator>) {
+        self.game_guid = validator;
+    }
+    pub fn game_guid(&self) -> Option<&GameGuidValidator> {
+        self.game_guid.as_ref()
+    }
+    /// Tracks players across reconnects with `sessions`, or `None` to treat
+    /// every connection as a new player. New players are picked by
+    /// [`NetEventListener::on_new_session`], and those that asked for one are
+    /// sent a [`ReconnectToken`]; [`NetPeer::join`] tells the listener which
+    /// player a peer is.
+    pub fn set_sessions(&mut self, sessions: Option<SessionRegistry>) {
+        self.sessions = sessions;
+    }
+    pub fn sessions(&self) -> Option<&SessionRegistry> {
+        self.sessions.as_ref()
+    }
+    /// For expiring dropped players, see [`SessionRegistry::expire`].
+    pub fn sessions_mut(&mut self) -> Option<&mut SessionRegistry> {
+        self.sessions.as_mut()
+    }
+    pub fn peer(&self, id: PeerId) -> Option<&NetPeer> {
+        self.peers.get(self.peer_addrs.get(&id)?)
+    }
+    pub fn peer_mut(&mut self, id: PeerId) -> Option<&mut NetPeer> {
+        self.peers.get_mut(self.peer_addrs.get(&id)?)
+    }
+    pub fn peers(&self) -> impl Iterator<Item = &NetPeer> {
+        self.peers.values().filter(|p| p.is_connected())
+    }
+    pub fn peer_count(&self) -> usize {
+        self.peers().count()
+    }
+    pub fn peer_stats(&self, id: PeerId) -> Option<PeerStats> {
+        self.peer(id).map(NetPeer::stats)
+    }
+    /// Traffic snapshots for every connected peer.
+    pub fn stats(&self) -> Vec<PeerStats> {
+        self.peers().map(NetPeer::stats).collect()
+    }
+    pub fn send(
+        &mut self,
+        peer: PeerId,
+        payload: &[u8],
+        options: SendOptions,
+    ) -> Result<(), SendError> {
+        self.peer_mut(peer)
+            .ok_or(SendError::NotConnected)?
+            .send(payload, options)
+    }
+    /// Sends `payload` to every connected peer `recipients` includes,
+    /// returning the peers it couldn't be queued for.
+    pub fn broadcast(
+        &mut self,
+        recipients: Recipients<'_>,
+        payload: &[u8],
+        options: SendOptions,
+    ) -> Vec<(PeerId, SendError)> {
+        self.peers
+            .values_mut()
+            .filter(|peer| peer.is_connected() && recipients.includes(peer))
+            .filter_map(|peer| {
+                let result = peer.send(payload, options);
+                result.err().map(|e| (peer.id(), e))
+            })
+            .collect()
+    }
+    /// Encodes an event message once and broadcasts it, see
+    /// [`NetServer::broadcast`].
+    pub fn broadcast_event(
+        &mut self,
+        recipients: Recipients<'_>,
+        code: NetworkEvent,
+        data: &impl ByteSerializeHeap,
+        options: SendOptions,
+    ) -> Result<Vec<(PeerId, SendError)>, EventError> {
+        if !code.accepted_by(Role::Client) {
+            return Err(EventError::WrongDirection(code));
+        }
+        let payload = encode_event(code, data)?;
+        Ok(self.broadcast(recipients, &payload, options))
+    }
+    /// Closes the connection to `peer`. The disconnect callback fires on the
+    /// next [`NetServer::poll`] with [`DisconnectReason::Shutdown`].
+    pub fn disconnect(&mut self, peer: PeerId) {
+        if let Some(peer) = self.peer_mut(peer) {
+            peer.close(peer.clock());
+        }
+    }
+    /// Disconnects every peer, raising their disconnect callbacks right away.
+    pub fn shutdown(&mut self, listener: &mut impl NetEventListener) {
+        for peer in self.peers.values_mut() {
+            peer.close(peer.clock());
+        }
+        self.remove_disconnected(listener);
+    }
+    /// Receives all pending datagrams, dispatches them to `listener` and
+    /// flushes outgoing packets. Never blocks.
+    pub fn poll(&mut self, listener: &mut impl NetEventListener) -> io::Result<()> {
+        self.poll_at(Instant::now(), listener)
+    }
+    /// [`NetServer::poll`] with an explicit clock, so tests can drive
+    /// resends, pings and timeouts deterministically.
+    pub fn poll_at(
+        &mut self,
+        now: Instant,
+        listener: &mut impl NetEventListener,
+    ) -> io::Result<()> {
+        self.transport.advance(now);
+        let mut buf = std::mem::take(&mut self.recv_buf);
+        let result = loop {
+            match self.transport.recv_from(&mut buf) {
+                Ok(Some((len, addr))) => self.handle_datagram(addr, &buf[..len], now, listener),
+                Ok(None) => break Ok(()),
+                Err(e) => break Err(e),
+            }
+        };
+        self.recv_buf = buf;
+        result?;
+        if let Some(validator) = &mut self.game_guid {
+            for peer in self.peers.values_mut() {
+                validator.check_timeout(peer, now);
+            }
+        }
+        for peer in self.peers.values_mut() {
+            peer.update(now);
+        }
+        self.flush();
+        self.remove_disconnected(listener);
+        Ok(())
+    }
+
+    fn handle_datagram(
+        &mut self,
+        addr: SocketAddr,
+        data: &[u8],
+        now: Instant,
+        listener: &mut impl NetEventListener,
+    ) {
+        // Malformed datagrams are dropped, the same as LiteNetLib does.
+        let Ok(packet) = Packet::parse(data) else {
+            return;
+        };
+        if packet.header.property == PacketProperty::Broadcast {
+            if packet.payload == DISCOVERY_REQUEST {
+                self.answer_discovery(addr);
+            }
+            return;
+        }
+        if packet.header.property == PacketProperty::ConnectRequest {
+            self.handle_connect_request(addr, packet, now, listener);
+            return;
+        }
+        let Some(peer) = self.peers.get_mut(&addr) else {
+            return;
+        };
+        if packet.header.connection_number != peer.connection_number() {
+            return;
+        }
+        match packet.header.property {
+            PacketProperty::Disconnect => {
+                let Ok(disconnect) = DisconnectPacket::parse(packet.payload) else {
+                    return;
+                };
+                if disconnect.connection_time != peer.connection_time() {
+                    return;
+                }
+                peer.send_raw(PacketHeader::new(PacketProperty::ShutdownOk), &[]);
+                peer.set_disconnected(DisconnectReason::RemoteClose);
+            }
+            PacketProperty::ShutdownOk => peer.set_disconnected(DisconnectReason::RemoteClose),
+            _ if peer.is_connected() => {
+                peer.process_packet(packet, now);
+                for (payload, options) in peer.take_received() {
+                    // A rate limit may have just disconnected the peer.
+                    if !peer.is_connected() {
+                        break;
+                    }
+                    if let Some(limits) = &self.rate_limits
+                        && !enforce(peer, limits, &mut self.limiter, &payload, now)
+                    {
+                        continue;
+                    }
+                    // Events only clients should get, such as a client
+                    // claiming a kill, are dropped.
+                    if let Ok((code, _)) = decode_event(&payload)
+                        && !code.accepted_by(Role::Server)
+                    {
+                        continue;
+                    }
+                    if let Some(validator) = &mut self.game_guid
+                        && !validator.is_validated(peer.id())
+                    {
+                        let accepted = match decode_event(&payload) {
+                            Ok((NetworkEvent::ValidateGameGuid, body)) => {
+                                validator.validate(peer, body, now)
+                            }
+                            _ => false,
+                        };
+                        if !accepted {
+                            continue;
+                        }
+                    }
+                    listener.on_receive(peer, &payload, options);
+                }
+            }
+            _ => {}
+        }
+    }
+    fn handle_connect_request(
+        &mut self,
+        addr: SocketAddr,
+        packet: Packet<'_>,
+        now: Instant,
+        listener: &mut impl NetEventListener,
+    ) {
+        let request = match ConnectRequestPacket::parse(packet.payload) {
+            Ok(request) => request,
+            Err(PacketError::InvalidProtocol(_)) => {
+                let header = PacketHeader::new(PacketProperty::InvalidProtocol);
+                self.send_to(addr, &Packet::new(header, &[]).to_vec());
+                return;
+            }
+            Err(_) => return,
+        };
+        if let Some(peer) = self.peers.get_mut(&addr) {
+            match request.connection_time.cmp(&peer.connection_time()) {
+                // Our accept got lost, so the client is still asking.
+                std::cmp::Ordering::Equal if peer.is_connected() => {
+                    send_accept(peer);
+                    return;
+                }
+                std::cmp::Ordering::Greater => {
+                    // The client restarted and is connecting from the same
+                    // address; the old session is gone.
+                    peer.set_disconnected(DisconnectReason::RemoteClose);
+                    self.remove_disconnected(listener);
+                }
+                _ => return,
+            }
+        }
+        let pending = ConnectionRequest {
+            addr,
+            connection_time: request.connection_time,
+            data: request.data.to_vec(),
+        };
+        if !listener.on_connection_request(&pending) {
+            let mut reject = PacketHeader::new(PacketProperty::Disconnect);
+            reject.connection_number = packet.header.connection_number;
+            let mut body = Vec::new();
+            DisconnectPacket {
+                connection_time: request.connection_time,
+                data: &[],
+            }
+            .write(&mut body);
+            self.send_to(addr, &Packet::new(reject, &body).to_vec());
+            return;
+        }
+        let id = PeerId(self.next_peer_id);
+        self.next_peer_id = self.next_peer_id.wrapping_add(1);
+        let mut peer = NetPeer::new(
+            id,
+            addr,
+            request.connection_time,
+            packet.header.connection_number,
+            pending.data,
+            self.settings,
+            now,
+        );
+        peer.set_connected(now);
+        if let Some(validator) = &mut self.game_guid {
+            validator.connected(id, now);
+        }
+        if let Some(sessions) = &mut self.sessions {
+            let join = sessions.resume(id, peer.connect_data(), now).or_else(|| {
+                let session = listener.on_new_session(&peer)?;
+                let token = sessions.start(id, session);
+                Some(Join::New { session, token })
+            });
+            peer.set_join(join);
+        }
+        send_accept(&mut peer);
+        if let Some(Join::New { token, .. }) = peer.join()
+            && ReconnectToken::requested(peer.connect_data())
+        {
+            // The peer was only just accepted, so this can't fail.
+            let _ = peer.send(&token.message(), SendOptions::ReliableOrdered);
+        }
+        listener.on_peer_connected(&mut peer);
+        let join = peer.join();
+        self.peer_addrs.insert(id, addr);
+        self.peers.insert(addr, peer);
+        if let Some(Join::Resumed { session, replaced }) = join {
+            if let Some(old) = replaced.and_then(|old| self.peer_mut(old)) {
+                old.close(now);
+            }
+            let reconnected = PlayerId {
+                player: session.player_id,
+            };
+            // Best effort, like any broadcast: a peer with a full queue
+            // misses it.
+            let _ = self.broadcast_event(
+                Recipients::Except(id),
+                NetworkEvent::OnClientReconnected,
+                &reconnected,
+                SendOptions::ReliableOrdered,
+            );
+        }
+    }
+    fn answer_discovery(&mut self, addr: SocketAddr) {
+        let Some(info) = &self.discovery else {
+            return;
+        };
+        let mut body = Vec::new();
+        DiscoveryResponse {
+            info: info.clone(),
+            player_count: self.peer_count().try_into().unwrap_or(u16::MAX),
+        }
+        .write(&mut body);
+        let header = PacketHeader::new(PacketProperty::UnconnectedMessage);
+        self.send_to(addr, &Packet::new(header, &body).to_vec());
+    }
+    fn flush(&mut self) {
+        for peer in self.peers.values_mut() {
+            let addr = peer.addr();
+            for datagram in peer.drain_outgoing() {
+                send_datagram(&mut self.transport, addr, &datagram);
+            }
+        }
+    }
+    fn remove_disconnected(&mut self, listener: &mut impl NetEventListener) {
+        let gone = self
+            .peers
+            .iter()
+            .filter(|(_, p)| p.state() == ConnectionState::Disconnected)
+            .map(|(&addr, _)| addr)
+            .collect::<Vec<_>>();
+        for addr in gone {
+            let Some(mut peer) = self.peers.remove(&addr) else {
+                continue;
+            };
+            for datagram in peer.drain_outgoing() {
+                send_datagram(&mut self.transport, addr, &datagram);
+            }
+            self.peer_addrs.remove(&peer.id());
+            if let Some(validator) = &mut self.game_guid {
+                validator.forget(peer.id());
+            }
+            if let Some(sessions) = &mut self.sessions {
+                sessions.disconnected(peer.id(), peer.clock());
+            }
+            let reason = peer
+                .disconnect_reason()
+                .unwrap_or(DisconnectReason::Shutdown);
+            listener.on_peer_disconnected(&peer, reason);
+        }
+    }
+    fn send_to(&mut self, addr: SocketAddr, datagram: &[u8]) {
+        send_datagram(&mut self.transport, addr, datagram);
+    }
+}
+
+pub(crate) fn send_datagram(transport: &mut impl Transport, addr: SocketAddr, datagram: &[u8]) {
+    // UDP gives no delivery guarantee anyway; a failed send is just a lost
+    // packet and the reliable channels will retry.
+    let _ = transport.send_to(datagram, addr);
+}
+
+/// Applies `limits` to a message from `peer`, returning whether it may be
+/// delivered.
+fn enforce(
+    peer: &mut NetPeer,
+    limits: &RateLimits,
+    server: &mut ServerLimiter,
+    payload: &[u8],
+    now: Instant,
+) -> bool {
+    let first = match peer.limiter_mut().check(limits, server, payload, now) {
+        Verdict::Allow => return true,
+        Verdict::Overloaded => return false,
+        Verdict::Violation { first } => first,
+    };
+    match &limits.action {
+        LimitAction::Drop => {}
+        LimitAction::Warn(code) => {
+            if first {
+                // Failing to warn doesn't change that the message is dropped.
+                let _ =
+                    peer.send_event(NetworkEvent::WarnPlayer, code, SendOptions::ReliableOrdered);
+            }
+        }
+        LimitAction::Disconnect => peer.close(now),
+    }
+    false
+}
+
+fn send_accept(peer: &mut NetPeer) {
+    let mut body = Vec::new();
+    ConnectAcceptPacket {
+        connection_time: peer.connection_time(),
+        connection_number: peer.connection_number(),
+        reused_peer: false,
+    }
+    .write(&mut body);
+    peer.send_raw(PacketHeader::new(PacketProperty::ConnectAccept), &body);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        events::{CommandOnly, loading::GameGuid},
+        net::{
+            LimitAction, MemoryNetwork, MemoryTransport, NetClient, PlayerSession, RateLimit,
+            ReconnectToken, peer::tests::config,
+        },
+        types::{GameServerErrorCodes, StringCode},
+    };
+
+    const SERVER: SocketAddr = SocketAddr::new(
+        std::net::IpAddr::V4(std::net::Ipv4Addr::new(10, 0, 0, 1)),
+        1,
+    );
+
+    #[derive(Default)]
+    struct Events {
+        codes: Vec<NetworkEvent>,
+        disconnected: Option<DisconnectReason>,
+        next_player: u8,
+    }
+
+    impl NetEventListener for Events {
+        fn on_new_session(&mut self, _peer: &NetPeer) -> Option<PlayerSession> {
+            self.next_player += 1;
+            Some(PlayerSession {
+                player_id: self.next_player,
+                machine_id: self.next_player.into(),
+            })
+        }
+        fn on_peer_disconnected(&mut self, _peer: &NetPeer, reason: DisconnectReason) {
+            self.disconnected = Some(reason);
+        }
+        fn on_event(
+            &mut self,
+            _peer: &mut NetPeer,
+            code: NetworkEvent,
+            _body: &[u8],
+            _options: SendOptions,
+        ) {
+            self.codes.push(code);
+        }
+    }
+
+    /// A server and its clients on one memory network.
+    struct Match {
+        network: MemoryNetwork,
+        server: NetServer<MemoryTransport>,
+        on_server: Events,
+        clients: Vec<(NetClient<MemoryTransport>, Events)>,
+        now: Instant,
+    }
+
+    impl Match {
+        fn new() -> Self {
+            let network = MemoryNetwork::new();
+            let server = NetServer::new(network.bind(SERVER).unwrap(), config());
+            Self {
+                network,
+                server,
+                on_server: Events::default(),
+                clients: Vec::new(),
+                now: Instant::now(),
+            }
+        }
+        /// Connects a client from a new port, returning its index.
+        fn connect(&mut self, data: &[u8]) -> usize {
+            let port = self.clients.len() as u16 + 2;
+            let transport = self.network.bind(([10, 0, 0, 2], port).into()).unwrap();
+            let client = NetClient::new(transport, SERVER, config(), data);
+            self.clients.push((client, Events::default()));
+            self.step();
+            self.clients.len() - 1
+        }
+        /// Lets 100ms pass.
+        fn step(&mut self) {
+            for _ in 0..5 {
+                self.now += Duration::from_millis(20);
+                for (client, events) in &mut self.clients {
+                    client.poll_at(self.now, events).unwrap();
+                }
+                self.server.poll_at(self.now, &mut self.on_server).unwrap();
+            }
+        }
+        fn peer(&self, client: usize) -> &NetPeer {
+            let addr = self.clients[client].0.local_addr().unwrap();
+            self.server
+                .peers()
+                .find(|peer| peer.addr() == addr)
+                .unwrap()
+        }
+        fn send(&mut self, client: usize, code: NetworkEvent, data: &impl ByteSerializeHeap) {
+            self.clients[client]
+                .0
+                .send_event(code, data, SendOptions::ReliableOrdered)
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn broadcasts_reach_the_peers_the_filter_includes() {
+        let mut game = Match::new();
+        let clients = [game.connect(b""), game.connect(b""), game.connect(b"")];
+        let [first, second, third] = clients.map(|client| game.peer(client).id());
+        for (peer, team) in [(first, 1), (second, 1), (third, 2)] {
+            game.server.peer_mut(peer).unwrap().set_team(Some(team));
+        }
+        let is_third = |peer: &NetPeer| peer.id() == third;
+        for (recipients, code) in [
+            (Recipients::All, NetworkEvent::GameStarted),
+            (Recipients::Team(1), NetworkEvent::MapPingEvent),
+            (Recipients::Except(first), NetworkEvent::FireWeaponEffect),
+            (Recipients::Matching(&is_third), NetworkEvent::TeamBaseState),
+        ] {
+            let failed = game
+                .server
+                .broadcast_event(recipients, code, &CommandOnly, SendOptions::ReliableOrdered)
+                .unwrap();
+            assert!(failed.is_empty());
+        }
+        game.step();
+        let received = clients.map(|client| game.clients[client].1.codes.clone());
+        assert_eq!(
+            received,
+            [
+                vec![NetworkEvent::GameStarted, NetworkEvent::MapPingEvent],
+                vec![
+                    NetworkEvent::GameStarted,
+                    NetworkEvent::MapPingEvent,
+                    NetworkEvent::FireWeaponEffect
+                ],
+                vec![
+                    NetworkEvent::GameStarted,
+                    NetworkEvent::FireWeaponEffect,
+                    NetworkEvent::TeamBaseState
+                ],
+            ]
+        );
+    }
+
+    fn ping_limits(action: LimitAction) -> RateLimits {
+        RateLimits::new(action).with_event(NetworkEvent::MapPingEvent, RateLimit::new(1.0, 1.0))
+    }
+
+    #[test]
+    fn warns_once_per_burst_of_violations() {
+        let mut game = Match::new();
+        let warning = StringCode {
+            ty: GameServerErrorCodes::StrErrHaxFirerate,
+            custom: None,
+        };
+        game.server
+            .set_rate_limits(Some(ping_limits(LimitAction::Warn(warning))));
+        let client = game.connect(b"");
+        for _ in 0..2 {
+            for _ in 0..3 {
+                game.send(client, NetworkEvent::MapPingEvent, &CommandOnly);
+            }
+            // Long enough for the bucket to refill.
+            for _ in 0..10 {
+                game.step();
+            }
+        }
+        assert_eq!(
+            game.on_server.codes,
+            [NetworkEvent::MapPingEvent, NetworkEvent::MapPingEvent]
+        );
+        assert_eq!(
+            game.clients[client].1.codes,
+            [NetworkEvent::WarnPlayer, NetworkEvent::WarnPlayer]
+        );
+        assert!(game.clients[client].0.is_connected());
+    }
+
+    #[test]
+    fn disconnects_peers_over_the_limit() {
+        let mut game = Match::new();
+        game.server
+            .set_rate_limits(Some(ping_limits(LimitAction::Disconnect)));
+        let client = game.connect(b"");
+        for _ in 0..2 {
+            game.send(client, NetworkEvent::MapPingEvent, &CommandOnly);
+        }
+        game.step();
+        assert_eq!(game.on_server.codes, [NetworkEvent::MapPingEvent]);
+        assert_eq!(
+            game.on_server.disconnected,
+            Some(DisconnectReason::Shutdown)
+        );
+        assert_eq!(game.server.peer_count(), 0);
+        assert_eq!(
+            game.clients[client].1.disconnected,
+            Some(DisconnectReason::RemoteClose)
+        );
+    }
+
+    #[test]
+    fn silent_clients_time_out() {
+        let mut game = Match::new();
+        let client = game.connect(b"");
+        let timeout = PeerSettings::new(&config(), Role::Server).disconnect_timeout;
+        // The client stops answering.
+        let (mut client, mut on_client) = game.clients.remove(client);
+        for _ in 0..2 {
+            game.step();
+        }
+        assert!(game.on_server.disconnected.is_none());
+        let deadline = game.now + timeout;
+        while game.now < deadline {
+            game.step();
+        }
+        assert_eq!(game.on_server.disconnected, Some(DisconnectReason::Timeout));
+        assert_eq!(game.server.peer_count(), 0);
+        // Nothing told the client, and the server no longer answers it, so it
+        // finds out the same way once the pings already on their way run out.
+        let deadline = game.now + timeout * 2;
+        while game.now < deadline && client.is_connected() {
+            game.step();
+            client.poll_at(game.now, &mut on_client).unwrap();
+        }
+        assert_eq!(on_client.disconnected, Some(DisconnectReason::Timeout));
+        assert!(!client.is_connected());
+    }
+
+    fn guid_match() -> Match {
+        let mut game = Match::new();
+        let validator = GameGuidValidator::new("5A0C-guid").with_timeout(Duration::from_secs(1));
+        game.server.set_game_guid(Some(validator));
+        game
+    }
+
+    fn guid(guid: &str) -> GameGuid {
+        GameGuid {
+            guid: guid.to_owned().into(),
+        }
+    }
+
+    #[test]
+    fn validates_the_game_guid() {
+        let mut game = guid_match();
+        let client = game.connect(b"");
+        game.send(client, NetworkEvent::MapPingEvent, &CommandOnly);
+        game.send(client, NetworkEvent::ValidateGameGuid, &guid("5a0c-GUID"));
+        game.send(client, NetworkEvent::MapPingEvent, &CommandOnly);
+        game.step();
+        // Nothing gets through before the GUID does.
+        assert_eq!(
+            game.on_server.codes,
+            [NetworkEvent::ValidateGameGuid, NetworkEvent::MapPingEvent]
+        );
+        assert_eq!(
+            game.clients[client].1.codes,
+            [NetworkEvent::GameGuidValidated]
+        );
+        let peer = game.peer(client).id();
+        assert!(game.server.game_guid().unwrap().is_validated(peer));
+        // Validated peers stay past the timeout.
+        for _ in 0..15 {
+            game.step();
+        }
+        assert!(game.clients[client].0.is_connected());
+    }
+
+    #[test]
+    fn rejects_the_wrong_game_guid() {
+        let mut game = guid_match();
+        let client = game.connect(b"");
+        game.send(
+            client,
+            NetworkEvent::ValidateGameGuid,
+            &guid("another battle"),
+        );
+        game.step();
+        assert!(game.on_server.codes.is_empty());
+        assert_eq!(game.clients[client].1.codes, [NetworkEvent::WarnPlayer]);
+        assert_eq!(
+            game.clients[client].1.disconnected,
+            Some(DisconnectReason::RemoteClose)
+        );
+    }
+
+    #[test]
+    fn drops_peers_that_never_validate() {
+        let mut game = guid_match();
+        let client = game.connect(b"");
+        for _ in 0..5 {
+            game.step();
+        }
+        assert!(game.clients[client].0.is_connected());
+        for _ in 0..10 {
+            game.step();
+        }
+        assert_eq!(game.clients[client].1.codes, [NetworkEvent::WarnPlayer]);
+        assert_eq!(
+            game.clients[client].1.disconnected,
+            Some(DisconnectReason::RemoteClose)
+        );
+        assert_eq!(game.server.peer_count(), 0);
+    }
+
+    #[test]
+    fn drops_events_sent_the_wrong_way() {
+        let mut game = Match::new();
+        let client = game.connect(b"");
+        let peer = game.peer(client).id();
+
+        // Raw sends skip the direction check `send_event` makes.
+        let player = PlayerId { player: 1 };
+        for code in [NetworkEvent::ConfirmedKill, NetworkEvent::EnemySpotted] {
+            let payload = encode_event(code, &player).unwrap();
+            game.clients[client]
+                .0
+                .send(&payload, SendOptions::ReliableOrdered)
+                .unwrap();
+            game.server
+                .send(peer, &payload, SendOptions::ReliableOrdered)
+                .unwrap();
+        }
+        game.step();
+        assert_eq!(game.on_server.codes, [NetworkEvent::EnemySpotted]);
+        assert_eq!(game.clients[client].1.codes, [NetworkEvent::ConfirmedKill]);
+    }
+
+    fn session_match() -> Match {
+        let mut game = Match::new();
+        game.server
+            .set_sessions(Some(SessionRegistry::new(Duration::from_secs(30))));
+        game
+    }
+
+    #[test]
+    fn reconnecting_player_keeps_their_session() {
+        let mut game = session_match();
+        let watcher = game.connect(b"");
+        let leaver = game.connect(&ReconnectToken::request());
+        let Some(Join::New { session, token }) = game.peer(leaver).join() else {
+            panic!("expected a new session");
+        };
+        assert_eq!(game.clients[leaver].0.reconnect_token(), Some(token));
+
+        game.clients[leaver].0.disconnect();
+        game.step();
+        let back = game.connect(&token.connect_data());
+        assert_eq!(
+            game.peer(back).join(),
+            Some(Join::Resumed {
+                session,
+                replaced: None
+            })
+        );
+        assert_eq!(game.clients[back].0.reconnect_token(), None);
+        assert_eq!(
+            game.clients[watcher].1.codes,
+            [NetworkEvent::OnClientReconnected]
+        );
+    }
+
+    #[test]
+    fn only_clients_that_opt_in_take_part_in_sessions() {
+        let mut game = session_match();
+        let player = game.connect(&ReconnectToken::request());
+        let Some(Join::New { token, .. }) = game.peer(player).join() else {
+            panic!("expected a new session");
+        };
+        // A game client sends connect data of its own, which may happen to
+        // be a token's length.
+        let raw_token = &token.connect_data()[ReconnectToken::request().len()..];
+        let fresh = game.connect(raw_token);
+        assert!(matches!(game.peer(fresh).join(), Some(Join::New { .. })));
+        assert!(game.clients[player].0.is_connected());
+        // And isn't sent a token it wouldn't understand.
+        assert_eq!(game.clients[fresh].0.reconnect_token(), None);
+        let stats = game.peer(fresh).stats();
+        assert_eq!(stats.reliable_ordered.packets_sent, 0);
+        assert!(game.clients[player].1.codes.is_empty());
+    }
+}
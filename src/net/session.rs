@@ -0,0 +1,221 @@
+use std::{
+    collections::HashMap,
+    hash::{BuildHasher, RandomState},
+    time::{Duration, Instant},
+};
+
+use crate::net::PeerId;
+
+/// Lets a dropped player rejoin as themselves.
+///
+/// Only clients that opt in take part: one that connects with
+/// [`ReconnectToken::request`] as its connect data is sent a token, and
+/// presents it with [`ReconnectToken::connect_data`] to reconnect. Game
+/// clients know nothing of this, so they join as usual and are never sent
+/// anything they don't expect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ReconnectToken(u64);
+
+// Starts the connect data of clients that opt in, versioned so the format
+// can change. Plain connect data is never read as a token.
+const CONNECT_MARKER: [u8; 5] = *b"RLNL\x01";
+// Not an event code, so the token message can't be mistaken for an event.
+const TOKEN_MESSAGE: u8 = 0xFF;
+
+impl ReconnectToken {
+    /// Connect data for a first connection, asking for a token.
+    pub fn request() -> Vec<u8> {
+        CONNECT_MARKER.to_vec()
+    }
+    /// Connect data for rejoining with this token.
+    pub fn connect_data(self) -> Vec<u8> {
+        let mut data = Self::request();
+        data.extend_from_slice(&self.0.to_le_bytes());
+        data
+    }
+    /// Whether `connect_data` opts in to reconnecting, with or without a
+    /// token.
+    pub fn requested(connect_data: &[u8]) -> bool {
+        connect_data.starts_with(&CONNECT_MARKER)
+    }
+    pub fn from_connect_data(connect_data: &[u8]) -> Option<Self> {
+        Self::read(connect_data.strip_prefix(&CONNECT_MARKER)?)
+    }
+    /// The message a server sends to hand a new player their token.
+    pub fn message(self) -> Vec<u8> {
+        let mut message = vec![TOKEN_MESSAGE];
+        message.extend_from_slice(&self.0.to_le_bytes());
+        message
+    }
+    pub fn from_message(payload: &[u8]) -> Option<Self> {
+        match payload.split_first()? {
+            (&TOKEN_MESSAGE, token) => Self::read(token),
+            _ => None,
+        }
+    }
+    fn read(bytes: &[u8]) -> Option<Self> {
+        Some(Self(u64::from_le_bytes(bytes.try_into().ok()?)))
+    }
+}
+
+/// Who a connection plays as in the match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PlayerSession {
+    pub player_id: u8,
+    pub machine_id: i16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Join {
+    /// A new player. Send them the token so they can come back later.
+    New {
+        session: PlayerSession,
+        token: ReconnectToken,
+    },
+    /// A player came back within the grace window. The rest of the match
+    /// should get `OnClientReconnected` for them rather than a new player.
+    Resumed {
+        session: PlayerSession,
+        /// The player's previous connection, if the server hadn't noticed it
+        /// drop yet. It should be disconnected.
+        replaced: Option<PeerId>,
+    },
+}
+
+#[derive(Debug)]
+struct Entry {
+    session: PlayerSession,
+    peer: Option<PeerId>,
+    dropped_at: Option<Instant>,
+}
+
+/// Tracks which player each connection is, keeping the seats of dropped
+/// players for a grace window.
+///
+/// Installed with [`NetServer::set_sessions`](crate::net::NetServer::set_sessions),
+/// the server joins every new peer, sends new players that asked for one
+/// their token and tells the rest of the match when a player comes back.
+#[derive(Debug)]
+pub struct SessionRegistry {
+    grace: Duration,
+    sessions: HashMap<ReconnectToken, Entry>,
+    tokens: HashMap<PeerId, ReconnectToken>,
+    random: RandomState,
+    issued: u64,
+}
+
+impl SessionRegistry {
+    pub fn new(grace: Duration) -> Self {
+        Self {
+            grace,
+            sessions: HashMap::new(),
+            tokens: HashMap::new(),
+            random: RandomState::new(),
+            issued: 0,
+        }
+    }
+    /// Whether `connect_data` holds a token that can still be resumed, for
+    /// deciding on connection requests.
+    pub fn can_resume(&self, connect_data: &[u8], now: Instant) -> bool {
+        ReconnectToken::from_connect_data(connect_data)
+            .and_then(|token| self.sessions.get(&token))
+            .is_some_and(|entry| self.in_grace(entry, now))
+    }
+    /// Binds a newly connected peer to a player: the one its token names if
+    /// that can still be resumed, otherwise a new one from `new_session`.
+    pub fn join(
+        &mut self,
+        peer: PeerId,
+        connect_data: &[u8],
+        now: Instant,
+        new_session: impl FnOnce() -> PlayerSession,
+    ) -> Join {
+        if let Some(join) = self.resume(peer, connect_data, now) {
+            return join;
+        }
+        let session = new_session();
+        Join::New {
+            session,
+            token: self.start(peer, session),
+        }
+    }
+    /// Binds a newly connected peer to the player its token names, if that
+    /// can still be resumed. Returns a [`Join::Resumed`].
+    pub fn resume(&mut self, peer: PeerId, connect_data: &[u8], now: Instant) -> Option<Join> {
+        let token = ReconnectToken::from_connect_data(connect_data).filter(|token| {
+            self.sessions
+                .get(token)
+                .is_some_and(|entry| self.in_grace(entry, now))
+        })?;
+        let entry = self.sessions.get_mut(&token)?;
+        let replaced = entry.peer.replace(peer);
+        entry.dropped_at = None;
+        let session = entry.session;
+        if let Some(old) = replaced {
+            self.tokens.remove(&old);
+        }
+        self.tokens.insert(peer, token);
+        Some(Join::Resumed { session, replaced })
+    }
+    /// Binds a newly connected peer to a new player, returning the token
+    /// it can come back with.
+    pub fn start(&mut self, peer: PeerId, session: PlayerSession) -> ReconnectToken {
+        let token = self.issue_token();
+        self.sessions.insert(
+            token,
+            Entry {
+                session,
+                peer: Some(peer),
+                dropped_at: None,
+            },
+        );
+        self.tokens.insert(peer, token);
+        token
+    }
+    /// Starts the grace window for a peer that went away.
+    pub fn disconnected(&mut self, peer: PeerId, now: Instant) {
+        let Some(token) = self.tokens.remove(&peer) else {
+            return;
+        };
+        if let Some(entry) = self.sessions.get_mut(&token) {
+            entry.peer = None;
+            entry.dropped_at = Some(now);
+        }
+    }
+    /// Forgets players whose grace window has run out, returning them so
+    /// their seats can be freed.
+    pub fn expire(&mut self, now: Instant) -> Vec<PlayerSession> {
+        let grace = self.grace;
+        let mut expired = Vec::new();
+        self.sessions.retain(|_, entry| {
+            let keep = entry
+                .dropped_at
+                .is_none_or(|dropped| now.duration_since(dropped) <= grace);
+            if !keep {
+                expired.push(entry.session);
+            }
+            keep
+        });
+        expired
+    }
+    pub fn session(&self, peer: PeerId) -> Option<PlayerSession> {
+        let token = self.tokens.get(&peer)?;
+        Some(self.sessions.get(token)?.session)
+    }
+    fn in_grace(&self, entry: &Entry, now: Instant) -> bool {
+        entry
+            .dropped_at
+            .is_none_or(|dropped| now.duration_since(dropped) <= self.grace)
+    }
+    fn issue_token(&mut self) -> ReconnectToken {
+        // RandomState is keyed from OS randomness, so tokens can't be guessed
+        // from ones seen before.
+        loop {
+            self.issued += 1;
+            let token = ReconnectToken(self.random.hash_one(self.issued));
+            if !self.sessions.contains_key(&token) {
+                return token;
+            }
+        }
+    }
+}
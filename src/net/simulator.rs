@@ -0,0 +1,154 @@
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    io,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use crate::net::transport::Transport;
+
+/// How badly a [`SimulatedTransport`] treats incoming datagrams.
+/// Probabilities are in `0.0..=1.0`; the default is a perfect link.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct NetworkConditions {
+    pub loss: f64,
+    pub latency: Duration,
+    /// Up to this much is randomly added to `latency` per datagram, which
+    /// also reorders datagrams sent close together.
+    pub jitter: Duration,
+    pub duplicate: f64,
+    /// Chance that a datagram is held back an extra `reorder_delay`, letting
+    /// later ones overtake it.
+    pub reorder: f64,
+    pub reorder_delay: Duration,
+}
+
+/// xorshift64*, which is plenty for picking which packets to mangle and
+/// keeps runs reproducible across platforms and dependency versions.
+#[derive(Debug, Clone)]
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // The state must never be zero.
+        Self((seed ^ 0x9e37_79b9_7f4a_7c15) | 1)
+    }
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+    /// Uniform in `0.0..1.0`.
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+    fn chance(&mut self, probability: f64) -> bool {
+        self.next_f64() < probability
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Delayed {
+    deliver_at: Instant,
+    // Keeps datagrams due at the same instant in arrival order.
+    order: u64,
+    from: SocketAddr,
+    datagram: Vec<u8>,
+}
+
+/// Wraps another transport and degrades what it receives according to
+/// [`NetworkConditions`]. Wrap both ends to affect both directions.
+///
+/// Time only moves through [`Transport::advance`], so with a fixed seed and
+/// a clock driven by `poll_at` every run sees the same losses and delays.
+#[derive(Debug)]
+pub struct SimulatedTransport<T> {
+    inner: T,
+    conditions: NetworkConditions,
+    rng: Rng,
+    now: Option<Instant>,
+    delayed: BinaryHeap<Reverse<Delayed>>,
+    next_order: u64,
+}
+
+impl<T: Transport> SimulatedTransport<T> {
+    pub fn new(inner: T, conditions: NetworkConditions, seed: u64) -> Self {
+        Self {
+            inner,
+            conditions,
+            rng: Rng::new(seed),
+            now: None,
+            delayed: BinaryHeap::new(),
+            next_order: 0,
+        }
+    }
+    pub fn conditions(&self) -> &NetworkConditions {
+        &self.conditions
+    }
+    pub fn conditions_mut(&mut self) -> &mut NetworkConditions {
+        &mut self.conditions
+    }
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+    fn delay(&mut self) -> Duration {
+        let c = self.conditions;
+        let mut delay = c.latency + c.jitter.mul_f64(self.rng.next_f64());
+        if self.rng.chance(c.reorder) {
+            delay += c.reorder_delay;
+        }
+        delay
+    }
+    fn hold(&mut self, now: Instant, from: SocketAddr, datagram: Vec<u8>) {
+        let deliver_at = now + self.delay();
+        self.delayed.push(Reverse(Delayed {
+            deliver_at,
+            order: self.next_order,
+            from,
+            datagram,
+        }));
+        self.next_order += 1;
+    }
+}
+
+impl<T: Transport> Transport for SimulatedTransport<T> {
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+    fn send_to(&mut self, datagram: &[u8], addr: SocketAddr) -> io::Result<()> {
+        self.inner.send_to(datagram, addr)
+    }
+    fn recv_from(&mut self, buf: &mut [u8]) -> io::Result<Option<(usize, SocketAddr)>> {
+        let now = *self.now.get_or_insert_with(Instant::now);
+        while let Some((len, from)) = self.inner.recv_from(buf)? {
+            if self.rng.chance(self.conditions.loss) {
+                continue;
+            }
+            let datagram = buf[..len].to_vec();
+            if self.rng.chance(self.conditions.duplicate) {
+                self.hold(now, from, datagram.clone());
+            }
+            self.hold(now, from, datagram);
+        }
+        if self
+            .delayed
+            .peek()
+            .is_none_or(|Reverse(next)| next.deliver_at > now)
+        {
+            return Ok(None);
+        }
+        let Reverse(next) = self.delayed.pop().unwrap();
+        let len = next.datagram.len().min(buf.len());
+        buf[..len].copy_from_slice(&next.datagram[..len]);
+        Ok(Some((len, next.from)))
+    }
+    fn advance(&mut self, now: Instant) {
+        self.now = Some(now);
+        self.inner.advance(now);
+    }
+}
@@ -0,0 +1,240 @@
+use std::{collections::HashMap, time::Duration};
+
+use crate::{
+    event_code::NetworkEvent,
+    net::{PeerId, SendOptions},
+};
+
+/// Packets on the wire for one delivery method, headers included. Resent
+/// and duplicate packets are counted every time.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChannelStats {
+    pub packets_sent: u64,
+    pub bytes_sent: u64,
+    pub packets_received: u64,
+    pub bytes_received: u64,
+}
+
+/// Whole messages of one [`NetworkEvent`], counted by payload size.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EventStats {
+    pub messages_sent: u64,
+    pub bytes_sent: u64,
+    pub messages_received: u64,
+    pub bytes_received: u64,
+}
+
+/// A snapshot of one connection's traffic.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerStats {
+    pub peer: PeerId,
+    pub unreliable: ChannelStats,
+    pub reliable_unordered: ChannelStats,
+    pub sequenced: ChannelStats,
+    pub reliable_ordered: ChannelStats,
+    /// Reliable packets sent again because no ack arrived in time.
+    pub resends: u64,
+    /// Resends as a percentage of all reliable packets sent, the same
+    /// estimate LiteNetLib reports.
+    pub packet_loss: f64,
+    pub rtt: Option<Duration>,
+    pub events: HashMap<NetworkEvent, EventStats>,
+}
+
+impl PeerStats {
+    pub(crate) fn new(peer: PeerId) -> Self {
+        Self {
+            peer,
+            unreliable: ChannelStats::default(),
+            reliable_unordered: ChannelStats::default(),
+            sequenced: ChannelStats::default(),
+            reliable_ordered: ChannelStats::default(),
+            resends: 0,
+            packet_loss: 0.0,
+            rtt: None,
+            events: HashMap::new(),
+        }
+    }
+    pub fn channel(&self, options: SendOptions) -> &ChannelStats {
+        match options {
+            SendOptions::Unreliable => &self.unreliable,
+            SendOptions::ReliableUnordered => &self.reliable_unordered,
+            SendOptions::Sequenced => &self.sequenced,
+            SendOptions::ReliableOrdered => &self.reliable_ordered,
+        }
+    }
+    pub(crate) fn channel_mut(&mut self, options: SendOptions) -> &mut ChannelStats {
+        match options {
+            SendOptions::Unreliable => &mut self.unreliable,
+            SendOptions::ReliableUnordered => &mut self.reliable_unordered,
+            SendOptions::Sequenced => &mut self.sequenced,
+            SendOptions::ReliableOrdered => &mut self.reliable_ordered,
+        }
+    }
+    /// Sum over all delivery methods.
+    pub fn total(&self) -> ChannelStats {
+        [
+            self.unreliable,
+            self.reliable_unordered,
+            self.sequenced,
+            self.reliable_ordered,
+        ]
+        .into_iter()
+        .fold(ChannelStats::default(), |total, c| ChannelStats {
+            packets_sent: total.packets_sent + c.packets_sent,
+            bytes_sent: total.bytes_sent + c.bytes_sent,
+            packets_received: total.packets_received + c.packets_received,
+            bytes_received: total.bytes_received + c.bytes_received,
+        })
+    }
+    pub(crate) fn record_event_sent(&mut self, code: NetworkEvent, bytes: usize) {
+        let event = self.events.entry(code).or_default();
+        event.messages_sent += 1;
+        event.bytes_sent += bytes as u64;
+    }
+    pub(crate) fn record_event_received(&mut self, code: NetworkEvent, bytes: usize) {
+        let event = self.events.entry(code).or_default();
+        event.messages_received += 1;
+        event.bytes_received += bytes as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::SocketAddr,
+        time::{Duration, Instant},
+    };
+
+    use super::*;
+    use crate::net::{
+        MemoryTransport, NetClient, NetEventListener, NetServer, NetworkConditions,
+        SimulatedTransport,
+        peer::tests::{config, deliver, pair},
+    };
+
+    #[test]
+    fn nothing_sent_means_no_loss() {
+        let (server, _) = pair(Instant::now());
+        let stats = server.stats();
+        assert_eq!(stats.resends, 0);
+        assert_eq!(stats.packet_loss, 0.0);
+        assert_eq!(stats.total(), ChannelStats::default());
+    }
+
+    #[test]
+    fn counts_packets_and_bytes_per_channel() {
+        let now = Instant::now();
+        let (mut server, mut client) = pair(now);
+        let ping = [NetworkEvent::MapPingEvent as u8, 1, 2, 3, 4];
+        for options in [
+            SendOptions::Unreliable,
+            SendOptions::ReliableOrdered,
+            SendOptions::Sequenced,
+        ] {
+            server.send(&ping, options).unwrap();
+        }
+        // Not an event message.
+        server.send(&[0xFF; 3], SendOptions::Unreliable).unwrap();
+        deliver(&mut server, &mut client, now);
+
+        let sent = server.stats();
+        let received = client.stats();
+        let unreliable = ChannelStats {
+            packets_sent: 2,
+            bytes_sent: 6 + 4,
+            ..ChannelStats::default()
+        };
+        let channeled = ChannelStats {
+            packets_sent: 1,
+            bytes_sent: 9,
+            ..ChannelStats::default()
+        };
+        assert_eq!(sent.unreliable, unreliable);
+        assert_eq!(sent.reliable_ordered, channeled);
+        assert_eq!(sent.sequenced, channeled);
+        assert_eq!(sent.reliable_unordered, ChannelStats::default());
+        let flip = |c: ChannelStats| ChannelStats {
+            packets_received: c.packets_sent,
+            bytes_received: c.bytes_sent,
+            ..ChannelStats::default()
+        };
+        assert_eq!(received.unreliable, flip(unreliable));
+        assert_eq!(received.reliable_ordered, flip(channeled));
+        assert_eq!(received.sequenced, flip(channeled));
+        // Only payloads that are event messages are counted per event.
+        let pings = EventStats {
+            messages_sent: 3,
+            bytes_sent: 15,
+            ..EventStats::default()
+        };
+        assert_eq!(
+            sent.events,
+            HashMap::from([(NetworkEvent::MapPingEvent, pings)])
+        );
+        assert_eq!(
+            received.events[&NetworkEvent::MapPingEvent],
+            EventStats {
+                messages_received: 3,
+                bytes_received: 15,
+                ..EventStats::default()
+            }
+        );
+    }
+
+    struct Quiet;
+
+    impl NetEventListener for Quiet {}
+
+    /// Sends reliable messages from a server to a client whose end drops
+    /// `loss` of what arrives, returning the server's stats.
+    fn send_over(loss: f64) -> PeerStats {
+        let server_addr = SocketAddr::from(([10, 0, 0, 1], 1));
+        let (server_transport, client_transport) =
+            MemoryTransport::pair(server_addr, SocketAddr::from(([10, 0, 0, 2], 2)));
+        let mut server = NetServer::new(server_transport, config());
+        let conditions = NetworkConditions {
+            loss,
+            latency: Duration::from_millis(20),
+            ..NetworkConditions::default()
+        };
+        let mut client = NetClient::new(
+            SimulatedTransport::new(client_transport, conditions, 7),
+            server_addr,
+            config(),
+            b"",
+        );
+        let mut now = Instant::now();
+        let mut sent = 0;
+        for _ in 0..300 {
+            now += Duration::from_millis(10);
+            client.poll_at(now, &mut Quiet).unwrap();
+            server.poll_at(now, &mut Quiet).unwrap();
+            let peer = server.peers().next().map(|peer| peer.id());
+            if let Some(peer) = peer
+                && sent < 100
+                && server
+                    .send(peer, b"hello", SendOptions::ReliableOrdered)
+                    .is_ok()
+            {
+                sent += 1;
+            }
+        }
+        assert_eq!(sent, 100);
+        server.stats().pop().unwrap()
+    }
+
+    #[test]
+    fn lossy_links_cause_resends() {
+        let clean = send_over(0.0);
+        assert_eq!(clean.resends, 0);
+        assert_eq!(clean.packet_loss, 0.0);
+        assert!(clean.rtt.is_some());
+
+        let lossy = send_over(0.3);
+        assert!(lossy.resends > 0);
+        let reliable_sent = lossy.reliable_ordered.packets_sent;
+        assert!(reliable_sent >= 100 + lossy.resends);
+        assert!(lossy.packet_loss > 0.0 && lossy.packet_loss < 100.0);
+    }
+}
@@ -0,0 +1,191 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+/// Moves datagrams between this endpoint and remote addresses.
+///
+/// Implementations never block: [`Transport::recv_from`] returns `Ok(None)`
+/// once nothing is pending.
+pub trait Transport {
+    fn local_addr(&self) -> io::Result<SocketAddr>;
+    fn send_to(&mut self, datagram: &[u8], addr: SocketAddr) -> io::Result<()>;
+    fn recv_from(&mut self, buf: &mut [u8]) -> io::Result<Option<(usize, SocketAddr)>>;
+    /// Called with the current time at the start of every poll, before any
+    /// datagrams are received.
+    fn advance(&mut self, _now: Instant) {}
+}
+
+#[derive(Debug)]
+pub struct UdpTransport {
+    socket: UdpSocket,
+}
+
+impl UdpTransport {
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let socket = UdpSocket::bind(addr)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self { socket })
+    }
+    /// Allows sending to broadcast addresses, for LAN discovery.
+    pub fn set_broadcast(&self, broadcast: bool) -> io::Result<()> {
+        self.socket.set_broadcast(broadcast)
+    }
+}
+
+impl Transport for UdpTransport {
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+    fn send_to(&mut self, datagram: &[u8], addr: SocketAddr) -> io::Result<()> {
+        self.socket.send_to(datagram, addr).map(|_| ())
+    }
+    fn recv_from(&mut self, buf: &mut [u8]) -> io::Result<Option<(usize, SocketAddr)>> {
+        loop {
+            match self.socket.recv_from(buf) {
+                Ok(received) => return Ok(Some(received)),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+                // ICMP port unreachable from a peer that went away surfaces as
+                // a reset on some platforms; it says nothing about our socket.
+                Err(e) if e.kind() == io::ErrorKind::ConnectionReset => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+type Inboxes = HashMap<SocketAddr, VecDeque<(SocketAddr, Vec<u8>)>>;
+
+/// An in-process network of [`MemoryTransport`]s, addressed like UDP
+/// sockets.
+///
+/// Delivery is lossless and in order, so a whole match can run in a test
+/// with the same results every time. Datagrams sent to an address nobody has
+/// bound are dropped.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryNetwork {
+    inboxes: Arc<Mutex<Inboxes>>,
+}
+
+impl MemoryNetwork {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Creates an endpoint at `addr`. Like a socket, the address is free
+    /// again once the endpoint is dropped.
+    pub fn bind(&self, addr: SocketAddr) -> io::Result<MemoryTransport> {
+        let mut inboxes = self.inboxes.lock().unwrap();
+        if inboxes.contains_key(&addr) {
+            return Err(io::ErrorKind::AddrInUse.into());
+        }
+        inboxes.insert(addr, VecDeque::new());
+        Ok(MemoryTransport {
+            addr,
+            network: self.clone(),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct MemoryTransport {
+    addr: SocketAddr,
+    network: MemoryNetwork,
+}
+
+impl MemoryTransport {
+    /// Two endpoints on a private network, one for each side of a
+    /// connection. Panics if `a == b`.
+    pub fn pair(a: SocketAddr, b: SocketAddr) -> (Self, Self) {
+        let network = MemoryNetwork::new();
+        let a = network.bind(a).unwrap();
+        let b = network
+            .bind(b)
+            .expect("a memory transport pair needs two distinct addresses");
+        (a, b)
+    }
+}
+
+impl Transport for MemoryTransport {
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.addr)
+    }
+    fn send_to(&mut self, datagram: &[u8], addr: SocketAddr) -> io::Result<()> {
+        if let Some(inbox) = self.network.inboxes.lock().unwrap().get_mut(&addr) {
+            inbox.push_back((self.addr, datagram.to_vec()));
+        }
+        Ok(())
+    }
+    fn recv_from(&mut self, buf: &mut [u8]) -> io::Result<Option<(usize, SocketAddr)>> {
+        let mut inboxes = self.network.inboxes.lock().unwrap();
+        let Some((from, datagram)) = inboxes.get_mut(&self.addr).and_then(VecDeque::pop_front)
+        else {
+            return Ok(None);
+        };
+        // Same as a UDP socket: whatever doesn't fit in `buf` is lost.
+        let len = datagram.len().min(buf.len());
+        buf[..len].copy_from_slice(&datagram[..len]);
+        Ok(Some((len, from)))
+    }
+}
+
+impl Drop for MemoryTransport {
+    fn drop(&mut self) {
+        if let Ok(mut inboxes) = self.network.inboxes.lock() {
+            inboxes.remove(&self.addr);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([10, 0, 0, 1], port))
+    }
+
+    #[test]
+    fn pair_delivers_in_order() {
+        let (mut a, mut b) = MemoryTransport::pair(addr(1), addr(2));
+        let mut buf = [0; 16];
+        assert!(b.recv_from(&mut buf).unwrap().is_none());
+        a.send_to(b"one", addr(2)).unwrap();
+        a.send_to(b"two", addr(2)).unwrap();
+        assert_eq!(b.recv_from(&mut buf).unwrap(), Some((3, addr(1))));
+        assert_eq!(&buf[..3], b"one");
+        assert_eq!(b.recv_from(&mut buf).unwrap(), Some((3, addr(1))));
+        assert_eq!(&buf[..3], b"two");
+        assert!(b.recv_from(&mut buf).unwrap().is_none());
+        assert!(a.recv_from(&mut buf).unwrap().is_none());
+    }
+
+    #[test]
+    fn truncates_to_the_buffer() {
+        let (mut a, mut b) = MemoryTransport::pair(addr(1), addr(2));
+        a.send_to(b"too long", addr(2)).unwrap();
+        let mut buf = [0; 3];
+        assert_eq!(b.recv_from(&mut buf).unwrap(), Some((3, addr(1))));
+        assert_eq!(&buf, b"too");
+        assert!(b.recv_from(&mut buf).unwrap().is_none());
+    }
+
+    #[test]
+    fn dropping_an_endpoint_frees_its_address() {
+        let network = MemoryNetwork::new();
+        let mut a = network.bind(addr(1)).unwrap();
+        let b = network.bind(addr(2)).unwrap();
+        assert_eq!(
+            network.bind(addr(2)).unwrap_err().kind(),
+            io::ErrorKind::AddrInUse
+        );
+        drop(b);
+        // Nobody is listening, so this is dropped rather than queued for the
+        // next endpoint at the address.
+        a.send_to(b"lost", addr(2)).unwrap();
+        let mut b = network.bind(addr(2)).unwrap();
+        assert!(b.recv_from(&mut [0; 16]).unwrap().is_none());
+    }
+}
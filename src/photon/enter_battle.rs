@@ -1,9 +1,10 @@
 use std::collections::HashMap;
 
 use polariton::operation::{Arr, Dict, ParameterTable, Typed};
+use strum::FromRepr;
 
 #[repr(u8)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, FromRepr, PartialEq, Eq, Hash)]
 pub enum GameModeType {
     BattleArena,
     SuddenDeath,
@@ -190,7 +191,7 @@ pub struct BattleParametersData {
     pub map_visibility: Option<i32>,
     pub is_autoheal: bool,
 }
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct GameModeKey {
     pub ty: GameModeType,
     pub is_ranked: bool,
@@ -144,23 +144,34 @@ impl ByteSerializeHeap for CubeStatus {
 #[derive(Debug, Default, Clone)]
 pub struct BinaryWriterString(String);
 const U8_HIGH_BIT: u8 = 0b10000000;
+const MAX_LENGTH_BYTES: u32 = 5;
 
 impl ByteDeserializeSlice<BinaryWriterString> for BinaryWriterString {
     fn byte_deserialize(
         des: &mut byteserde::prelude::ByteDeserializerSlice,
     ) -> byteserde::error::Result<BinaryWriterString> {
-        let mut len = 0usize;
-        let mut ct = 0;
-        loop {
-            let next = des.deserialize_u8()?;
-            len |= ((next & 0x7F) as usize) << ct;
-            ct += 7;
+        let bad_length = || SerDesError {
+            message: "Malformed C# string length".into(),
+        };
+        // A 7-bit encoded Int32, which takes at most 5 bytes.
+        let mut len = 0u32;
+        for i in 0..MAX_LENGTH_BYTES {
+            // deserialize_u8 panics at the end of the buffer instead of failing.
+            let next = des.deserialize_bytes_slice(1)?[0];
+            if i == MAX_LENGTH_BYTES - 1 && next > 0x0F {
+                return Err(bad_length());
+            }
+            len |= u32::from(next & 0x7F) << (7 * i);
             if next & U8_HIGH_BIT == 0 {
                 break;
             }
+            if i == MAX_LENGTH_BYTES - 1 {
+                return Err(bad_length());
+            }
         }
-        let slice = des.deserialize_bytes_slice(len * 2)?;
-        let iter = (0..len).map(|i| u16::from_be_bytes([slice[2 * i], slice[2 * i + 1]]));
+        let len = usize::try_from(len).map_err(|_| bad_length())?;
+        let slice = des.deserialize_bytes_slice(len.checked_mul(2).ok_or_else(bad_length)?)?;
+        let iter = (0..len).map(|i| u16::from_le_bytes([slice[2 * i], slice[2 * i + 1]]));
         Ok(Self(
             std::char::decode_utf16(iter)
                 .collect::<Result<String, _>>()
@@ -186,7 +197,7 @@ impl ByteSerializeHeap for BinaryWriterString {
             .collect::<Vec<_>>();
 
         while ctr >= U8_HIGH_BIT as usize {
-            ser.serialize_bytes_slice(&[ctr as u8 & U8_HIGH_BIT])?;
+            ser.serialize_bytes_slice(&[ctr as u8 | U8_HIGH_BIT])?;
             ctr >>= 7;
         }
         ser.serialize_bytes_slice(&[ctr as u8 & 0x7F])?;
@@ -206,6 +217,11 @@ impl DerefMut for BinaryWriterString {
         &mut self.0
     }
 }
+impl From<String> for BinaryWriterString {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
 #[derive(Debug, Default, Clone)]
 pub struct StringCode {
     pub ty: GameServerErrorCodes,
@@ -428,3 +444,43 @@ pub enum IngameStatId {
     BattleArenaObjectives,
 }
 enum_serialize! { IngameStatId, u8 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use byteserde::{des_slice::from_slice, prelude::ByteSerializerHeap};
+
+    use super::*;
+
+    pub(crate) fn to_bytes(value: &impl ByteSerializeHeap) -> Vec<u8> {
+        let mut ser = ByteSerializerHeap::default();
+        ser.serialize(value).unwrap();
+        ser.as_slice().to_vec()
+    }
+
+    #[test]
+    fn binary_writer_string_is_utf16_le() {
+        let bytes = to_bytes(&BinaryWriterString("hé".to_owned()));
+        assert_eq!(bytes, [2, b'h', 0, 0xe9, 0]);
+        assert_eq!(*from_slice::<BinaryWriterString>(&bytes).unwrap(), "hé");
+    }
+
+    #[test]
+    fn binary_writer_string_long_length_prefix() {
+        let long = "x".repeat(200);
+        let bytes = to_bytes(&BinaryWriterString(long.clone()));
+        // 200 in 7-bit groups, low group first with the continuation bit set
+        assert_eq!(bytes[..2], [0xc8, 0x01]);
+        assert_eq!(bytes.len(), 2 + 400);
+        assert_eq!(*from_slice::<BinaryWriterString>(&bytes).unwrap(), long);
+    }
+
+    #[test]
+    fn binary_writer_string_rejects_malformed_length() {
+        assert!(from_slice::<BinaryWriterString>(&[0xff; 12]).is_err());
+        // fifth byte with more than the 4 bits an Int32 has left
+        assert!(from_slice::<BinaryWriterString>(&[0xff, 0xff, 0xff, 0xff, 0x1f]).is_err());
+        // a valid but huge length with no data behind it
+        assert!(from_slice::<BinaryWriterString>(&[0xff, 0xff, 0xff, 0xff, 0x0f]).is_err());
+        assert!(from_slice::<BinaryWriterString>(&[0x80]).is_err());
+    }
+}